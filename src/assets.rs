@@ -0,0 +1,95 @@
+use anyhow::*;
+use cgmath::Vector2;
+use std::path::{Path, PathBuf};
+use tobj::LoadOptions;
+
+/// Directory with game assets, copied next to the build artifacts by `build.rs`
+pub fn res_dir() -> PathBuf {
+    Path::new(env!("OUT_DIR")).join("res")
+}
+
+/// Shared by the renderer and the catalogue, so that mesh ids match between the two
+pub(crate) fn obj_load_options() -> LoadOptions {
+    LoadOptions {
+        triangulate: true,
+        single_index: true,
+        ..Default::default()
+    }
+}
+
+pub struct MeshInfo {
+    pub name: String,
+    pub material: usize,
+    /// Outline of the mesh seen from above, see `mesh_hull`
+    pub hull: Vec<Vector2<f32>>,
+}
+
+/// Convex hull of a mesh projected onto the XY plane, which is the plane the game is played in
+pub(crate) fn mesh_hull(mesh: &tobj::Mesh) -> Vec<Vector2<f32>> {
+    let points = mesh
+        .positions
+        .chunks(3)
+        .map(|xyz| Vector2::new(xyz[0], xyz[1]))
+        .collect::<Vec<_>>();
+
+    crate::gamestate::geometry::convex_hull(&points)
+}
+
+/// Names and ids of the meshes in `assets.obj`, without any GPU resources.
+/// This is all the game logic needs to know about assets, so it can run headless.
+pub struct AssetCatalogue {
+    pub meshes: Vec<MeshInfo>,
+}
+
+impl AssetCatalogue {
+    pub fn load() -> Result<Self> {
+        Self::load_obj(res_dir().join("assets.obj"))
+    }
+
+    pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let (obj_models, _obj_materials) = tobj::load_obj(path.as_ref(), &obj_load_options())
+            .with_context(|| format!("Can't load {:?}", path.as_ref()))?;
+
+        let meshes = obj_models
+            .into_iter()
+            .map(|m| MeshInfo {
+                name: m.name,
+                material: m.mesh.material_id.unwrap_or(0),
+                hull: mesh_hull(&m.mesh),
+            })
+            .collect();
+
+        Ok(Self { meshes })
+    }
+
+    pub fn get_mesh_by_name(&self, name: &str) -> Option<(usize, &MeshInfo)> {
+        self.meshes.iter().enumerate().find_map(|(id, mesh)| {
+            if mesh.name == name {
+                Some((id, mesh))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+#[test]
+fn test_asset_catalogue_load() {
+    let catalogue = AssetCatalogue::load().unwrap();
+
+    for name in [
+        "Spaceship",
+        "Asteroid_S",
+        "Asteroid_M",
+        "Asteroid_L",
+        "Laser",
+        "Cloud_L",
+    ] {
+        assert!(catalogue.get_mesh_by_name(name).is_some(), "{}", name);
+    }
+    assert!(catalogue.get_mesh_by_name("Nonexistent").is_none());
+
+    for mesh in &catalogue.meshes {
+        assert!(mesh.hull.len() >= 3, "{}", mesh.name);
+    }
+}
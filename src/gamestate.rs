@@ -1,96 +1,144 @@
 mod collision;
+mod commands;
 pub mod components;
 mod entity;
+pub mod events;
 pub mod geometry;
+pub mod prefab;
+pub mod query;
+mod storage;
+mod waves;
 pub mod world;
 
 use crate::debug;
 use crate::instance::InstanceRaw;
 
-use crate::resource::Resources;
 use crate::shaders::ShaderName;
 use crate::{input::Input, instance::Instance};
 use cgmath::prelude::*;
 use rayon::iter::IntoParallelRefIterator;
-use rayon::iter::IntoParallelRefMutIterator;
 use rayon::iter::ParallelIterator;
 use shared::LightUniform;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::rc::Rc;
 use std::time::Duration;
 use std::time::Instant;
 
-use rand::Rng;
+use rand::{Rng, SeedableRng};
 
-use self::components::{Health, Renderable};
+use self::commands::{Command, Commands, Component, ComponentKind};
+use self::components::{
+    Ai, Control, Health, Hyperspace, Kind, Lifetime, Modifiers, Physics, Renderable, Shield, Weapon,
+};
 use self::entity::{Entity, EntityFactory};
-use self::world::World;
+use self::events::{Events, GameEvent};
+use self::geometry::Shape;
+use self::prefab::Prefabs;
+pub use self::storage::EntityId;
+use self::storage::EntityStorage;
+use self::waves::Waves;
+use self::world::{World, WorldPosition};
+
+/// Duration of a single simulation step. The simulation always advances by this amount,
+/// no matter how often frames are rendered.
+pub const FIXED_TIMESTEP: Duration = Duration::from_nanos(1_000_000_000 / 120);
+
+/// Frame time is clamped to this value, so that a long stall (e.g. window drag)
+/// doesn't make the simulation run hundreds of steps to catch up
+const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+
+/// Time between a saucer leaving the world and the next one showing up
+const SAUCER_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Ships the player starts a game with
+const LIVES: usize = 3;
+/// Time from losing a ship until the next one may appear
+const RESPAWN_DELAY: Duration = Duration::from_secs(2);
+/// A new ship only appears when there are no asteroids this close to the world centre
+const RESPAWN_CLEAR_RADIUS: f32 = 20.;
+
+/// The only source of randomness in the simulation.
+/// Seeded explicitly, so a session can be reproduced from its seed and inputs.
+pub type GameRng = rand_chacha::ChaCha8Rng;
 
 pub struct GameState {
-    entities: Vec<Option<Entity>>,
+    entities: EntityStorage,
     pub world: World,
     last_update: Instant,
+    accumulator: Duration,
+    rng: GameRng,
+    commands: Commands,
+    events: Events,
     score: usize,
+    lives: usize,
+    /// Set while waiting to bring the ship back after losing a life
+    respawn_in: Option<Duration>,
+    waves: Waves,
+    /// Time left until the next saucer shows up
+    saucer_in: Duration,
+    /// Sensors and whatever overlapped them during the last simulation step
+    overlaps: BTreeSet<(EntityId, EntityId)>,
     pub entity_factory: EntityFactory,
     pub cutscene_mode: bool,
 }
 
-#[allow(dead_code)]
-type EntityIndex = usize;
-
 type GroupedForRender<T> = BTreeMap<ShaderName, BTreeMap<usize, BTreeMap<usize, Vec<T>>>>;
 
 impl GameState {
-    pub fn new_game(aspect: f32, resources: Rc<Resources>, cutscene_mode: bool) -> Self {
-        let mut game = Self {
-            entities: vec![],
-            world: World::init(aspect),
-            last_update: Instant::now(),
-            score: 0,
-            entity_factory: EntityFactory { resources },
-            cutscene_mode,
-        };
-
-        let mut spaceship = game
-            .entity_factory
-            .make_spaceship(game.world.new_position((0.0, 0.0).into()), 0.);
+    pub fn new_game(aspect: f32, prefabs: Rc<Prefabs>, cutscene_mode: bool, seed: u64) -> Self {
+        let mut game = Self::empty(aspect, prefabs, cutscene_mode, seed);
+
+        let mut spaceship = game.entity_factory.make(
+            "Spaceship",
+            game.world.new_position((0.0, 0.0).into()),
+            cgmath::Quaternion::one(),
+            &mut game.rng,
+        );
 
         if cutscene_mode {
             spaceship.health = Some(Health {
-                level: 3,
                 invincible: true,
+                ..Health::new(3)
             });
             spaceship.renderable = None;
         }
 
         game.push(spaceship);
-
-        game.spawn_asteroid();
-        game.spawn_asteroid();
-        game.spawn_asteroid();
+        game.wave_system().apply_commands();
 
         game
     }
 
-    pub fn push(&mut self, entity: Entity) {
-        let first_vacant_id = self.entities.iter().enumerate().find_map(|(id, entity)| {
-            if Option::is_none(entity) {
-                Some(id)
-            } else {
-                None
-            }
-        });
-
-        match first_vacant_id {
-            Some(id) => self.entities[id] = Some(entity),
-            None => self.entities.push(Some(entity)),
+    /// A world without any entities
+    fn empty(aspect: f32, prefabs: Rc<Prefabs>, cutscene_mode: bool, seed: u64) -> Self {
+        Self {
+            entities: EntityStorage::default(),
+            world: World::init(aspect),
+            last_update: Instant::now(),
+            accumulator: Duration::ZERO,
+            rng: GameRng::seed_from_u64(seed),
+            commands: Commands::default(),
+            events: Events::default(),
+            score: 0,
+            lives: LIVES,
+            respawn_in: None,
+            waves: Waves::default(),
+            saucer_in: SAUCER_INTERVAL,
+            overlaps: BTreeSet::new(),
+            entity_factory: EntityFactory { prefabs },
+            cutscene_mode,
         }
     }
 
-    pub fn kill(&mut self, index: EntityIndex) {
-        self.entities[index] = None;
+    pub fn push(&mut self, entity: Entity) -> EntityId {
+        self.entities.insert(entity)
+    }
 
-        debug(&format!("Killing {}", index));
+    /// Does nothing if the entity is already dead
+    pub fn kill(&mut self, id: EntityId) {
+        self.entities.remove(id);
+
+        debug(&format!("Killing {:?}", id));
         debug(&format!("Entites: {:?}", self.entities));
     }
 
@@ -98,32 +146,55 @@ impl GameState {
         self.score
     }
 
+    pub fn lives(&self) -> usize {
+        self.lives
+    }
+
+    pub fn level(&self) -> usize {
+        self.waves.level()
+    }
+
+    /// The field has been cleared and the next level is about to start
+    pub fn is_between_levels(&self) -> bool {
+        self.waves.in_breather()
+    }
+
+    /// All entities of the kinds accepted by `kind_matches`, e.g. `Kind::is_asteroid`
+    pub fn entities_of_kind(
+        &self,
+        kind_matches: fn(Kind) -> bool,
+    ) -> impl Iterator<Item = (EntityId, &Entity)> {
+        self.entities
+            .iter_with_ids()
+            .filter(move |(_id, entity)| entity.is(kind_matches))
+    }
+
+    /// Whether the entity is alive and is of a kind accepted by `kind_matches`
+    pub fn is(&self, id: EntityId, kind_matches: fn(Kind) -> bool) -> bool {
+        self.get_entity(id)
+            .map(|entity| entity.is(kind_matches))
+            .unwrap_or(false)
+    }
+
+    pub fn is_shielded(&self, id: EntityId) -> bool {
+        matches!(self.get_entity(id), Some(Entity { shield: Some(shield), .. }) if shield.active)
+    }
+
+    pub fn spaceship(&self) -> Option<(EntityId, &Entity)> {
+        self.entities_of_kind(Kind::is_ship).next()
+    }
+
     pub fn spaceship_health(&self) -> Option<Health> {
-        self.entities.iter().flatten().find_map(|entity| {
-            if let Some(health) = entity.health {
-                if entity.name == "Spaceship" {
-                    Some(health)
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        })
+        self.spaceship().and_then(|(_id, entity)| entity.health)
     }
 
     pub fn is_over(&self) -> bool {
-        let health_level = self
-            .spaceship_health()
-            .map(|health| health.level)
-            .unwrap_or(0);
-
-        health_level == 0
+        self.lives == 0
     }
 
     pub fn spawn_asteroid(&mut self) {
         // Spawn outside of the world
-        let mut rng = rand::thread_rng();
+        let rng = &mut self.rng;
         let asteroid_radius = 5.;
         let (w, h) = self.world.size;
 
@@ -139,23 +210,66 @@ impl GameState {
             position.1 = (h / 2. + asteroid_radius) * if bottom { -1. } else { 1. };
         }
 
-        let mut asteroid = self
-            .entity_factory
-            .make_asteroid_l(self.world.new_position(position.into()));
+        let mut asteroid = self.entity_factory.make(
+            "Asteroid_L",
+            self.world.new_position(position.into()),
+            cgmath::Quaternion::one(),
+            &mut self.rng,
+        );
         let direction_towards_world_center = asteroid.position().to_vector2() * -1.;
         if let Some(physics) = &mut asteroid.physics {
-            physics.linear_speed =
-                direction_towards_world_center.normalize_to(physics.linear_speed.magnitude());
+            physics.linear_speed = direction_towards_world_center
+                .normalize_to(physics.linear_speed.magnitude() * self.waves.speed_factor());
+        }
+        self.commands.spawn(asteroid);
+    }
+
+    /// Saucers appear just outside the left or the right edge and cross the world
+    pub fn spawn_saucer(&mut self) {
+        let rng = &mut self.rng;
+        let (w, h) = self.world.size;
+        let saucer_radius = 5.;
+
+        // Small saucers get more common with every level
+        let small_chance = (0.1 * self.waves.level() as f64).min(0.7);
+        let name = if rng.gen_bool(small_chance) {
+            "Saucer_S"
+        } else {
+            "Saucer_L"
+        };
+        let from_left = rng.gen_bool(0.5);
+        let x = (w / 2. + saucer_radius) * if from_left { -1. } else { 1. };
+        let y = rng.gen_range(0.0..h) - h / 2.;
+
+        let mut saucer = self.entity_factory.make(
+            name,
+            self.world.new_position((x, y).into()),
+            cgmath::Quaternion::one(),
+            &mut self.rng,
+        );
+        if let Some(ai) = &mut saucer.ai {
+            ai.heading = cgmath::vec2(if from_left { 1. } else { -1. }, 0.);
+            ai.course_change_in = Duration::ZERO;
         }
-        self.push(asteroid);
+        self.commands.spawn(saucer);
     }
 
-    pub fn get_entity(&self, id: EntityIndex) -> Option<&Entity> {
-        self.entities.get(id).unwrap().as_ref()
+    /// Events published since the beginning of the current frame
+    pub fn events(&self) -> impl Iterator<Item = &GameEvent> {
+        self.events.iter()
     }
 
-    pub fn get_entity_mut(&mut self, id: EntityIndex) -> Option<&mut Entity> {
-        self.entities.get_mut(id).unwrap().as_mut()
+    pub fn drain_events(&mut self) -> Vec<GameEvent> {
+        self.events.drain()
+    }
+
+    /// Returns `None` if the entity has been killed, even if its slot is taken by another entity
+    pub fn get_entity(&self, id: EntityId) -> Option<&Entity> {
+        self.entities.get(id)
+    }
+
+    pub fn get_entity_mut(&mut self, id: EntityId) -> Option<&mut Entity> {
+        self.entities.get_mut(id)
     }
 
     pub fn entities_grouped_by_name(&self) -> Vec<(&str, Vec<&Entity>)> {
@@ -163,19 +277,17 @@ impl GameState {
         let mut group = Vec::new();
         let mut entity_name = "";
 
-        for entity in &self.entities {
-            if let Some(entity) = entity {
-                if entity_name == "" {
-                    entity_name = entity.name;
-                }
+        for entity in self.entities.iter() {
+            if entity_name == "" {
+                entity_name = entity.name;
+            }
 
-                if entity_name == entity.name {
-                    group.push(entity);
-                } else {
-                    groups.push((entity_name, group));
-                    entity_name = entity.name;
-                    group = vec![entity];
-                }
+            if entity_name == entity.name {
+                group.push(entity);
+            } else {
+                groups.push((entity_name, group));
+                entity_name = entity.name;
+                group = vec![entity];
             }
         }
 
@@ -186,11 +298,16 @@ impl GameState {
     pub fn entities_grouped(&self) -> GroupedForRender<&Entity> {
         let mut shaders_map: GroupedForRender<&Entity> = BTreeMap::new();
 
-        self.entities.iter().flatten().for_each(|entity| {
+        self.entities.iter().for_each(|entity| {
+            if entity.is_hidden() {
+                return;
+            }
+
             if let Some(Renderable {
                 shader,
                 mesh,
                 material,
+                ..
             }) = entity.renderable
             {
                 match shaders_map.get_mut(&shader) {
@@ -227,12 +344,13 @@ impl GameState {
 
     pub fn instances_grouped(&self) -> GroupedForRender<Instance> {
         let world = &self.world;
+        let alpha = self.interpolation_alpha();
         map_btreemap(&self.entities_grouped(), |mesh_map| {
             map_btreemap(mesh_map, |mat_map| {
                 map_btreemap(mat_map, |entities| {
                     entities
                         .par_iter()
-                        .map(|entity| world.add_ghost_instances(entity))
+                        .map(|entity| world.add_ghost_instances(entity, alpha))
                         .flatten()
                         .collect::<Vec<Instance>>()
                 })
@@ -254,16 +372,18 @@ impl GameState {
     }
 
     pub fn light_uniforms(&self) -> Vec<LightUniform> {
+        let alpha = self.interpolation_alpha();
         self.entities
             .iter()
-            .flatten()
+            .filter(|entity| !entity.is_hidden())
             .flat_map(|entity| {
-                entity.light.map(|light| {
+                let shield_light = entity.shield.and_then(|shield| shield.light());
+                entity.light.into_iter().chain(shield_light).map(|light| {
                     let mut rect = self.world.rect();
                     // Expending world rect so to fit lights which radius touches the visible space from the outside
                     rect.expand(light.radius);
 
-                    let instances = self.world.add_ghost_instances(entity);
+                    let instances = self.world.add_ghost_instances(entity, alpha);
                     instances
                         .par_iter()
                         .filter(|instance| rect.contains_point(instance.position.truncate().into()))
@@ -275,73 +395,187 @@ impl GameState {
             .collect::<Vec<_>>()
     }
 
+    /// Advance the simulation by the wall-clock time passed since the previous call.
+    /// Returns the number of simulation steps made.
+    pub fn update(&mut self, input: &Input) -> usize {
+        let now = Instant::now();
+        let frame_time = now - self.last_update;
+        self.last_update = now;
+
+        self.advance(frame_time, input)
+    }
+
+    /// Run as many fixed steps as fit into the accumulated time.
+    /// The remainder is carried over to the next frame and exposed as `interpolation_alpha`.
+    pub fn advance(&mut self, frame_time: Duration, input: &Input) -> usize {
+        self.events.clear();
+        self.accumulator += frame_time.min(MAX_FRAME_TIME);
+
+        let mut steps = 0;
+        while self.accumulator >= FIXED_TIMESTEP {
+            self.step(input);
+            self.accumulator -= FIXED_TIMESTEP;
+            steps += 1;
+        }
+
+        steps
+    }
+
+    /// Advance the simulation by exactly one `FIXED_TIMESTEP`
+    pub fn step(&mut self, input: &Input) {
+        self.entities
+            .iter_mut()
+            .for_each(|entity| entity.remember_transform());
+
+        self.weapon_system()
+            .control_system(input)
+            .apply_commands()
+            .hyperspace_system(input)
+            .apply_commands()
+            .shield_system(input)
+            .apply_commands()
+            .lifetime_system()
+            .apply_commands()
+            .health_system()
+            .wave_system()
+            .saucer_spawn_system()
+            .apply_commands()
+            .ai_system()
+            .apply_commands()
+            .physics_system()
+            .collision_system()
+            .apply_commands()
+            .lives_system()
+            .apply_commands();
+    }
+
+    /// Sync point: make changes requested by the previous system visible to the next one
+    pub fn apply_commands(&mut self) -> &mut Self {
+        for command in self.commands.drain() {
+            match command {
+                Command::Spawn(entity) => {
+                    self.push(*entity);
+                }
+                Command::Despawn(id) => {
+                    if let Some(entity) = self.get_entity(id) {
+                        self.events.publish(GameEvent::EntityDestroyed {
+                            id,
+                            name: entity.name,
+                            position: entity.position(),
+                        });
+                    }
+                    self.kill(id)
+                }
+                Command::Insert(id, component) => {
+                    debug(&format!("Inserting {:?} into {:?}", component.kind(), id));
+                    if let Some(entity) = self.get_entity_mut(id) {
+                        entity.insert(component);
+                    }
+                }
+                Command::Remove(id, kind) => {
+                    if let Some(entity) = self.get_entity_mut(id) {
+                        if entity.remove(kind).is_some() {
+                            debug(&format!("Removed {:?} from {:?}", kind, id));
+                        }
+                    }
+                }
+                Command::AddScore(points) => {
+                    self.score += points;
+                    self.events
+                        .publish(GameEvent::ScoreChanged { score: self.score });
+                }
+            }
+        }
+
+        self
+    }
+
+    /// How far rendering is between the previous and the current simulation step, in `0.0..1.0`
+    pub fn interpolation_alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / FIXED_TIMESTEP.as_secs_f32()
+    }
+
     fn delta_time(&self) -> Duration {
-        self.last_update.elapsed()
+        FIXED_TIMESTEP
     }
 
     pub fn control_system(&mut self, input: &Input) -> &mut Self {
-        let mut to_spawn = vec![];
-
         let delta_time = self.delta_time();
-        for option_entity in &mut self.entities {
-            if let Some(entity) = option_entity {
-                let position = entity.position();
-                match (&mut entity.control, &mut entity.physics) {
-                    (Some(control), Some(physics)) => {
-                        if control.enabled {
-                            let rotation_speed = 180.;
-                            let linear_acceleration = 50.;
-                            {
-                                let dtime = (delta_time.as_millis() as f32) / 1000.0;
-                                let delta_angle = dtime * rotation_speed;
-                                let delta_linear_speed = dtime * linear_acceleration;
-
-                                let direction = entity
-                                    .rotation
-                                    .rotate_vector(cgmath::Vector3::unit_y())
-                                    .truncate();
-
-                                if input.is_forward_pressed {
-                                    physics.linear_speed += direction * delta_linear_speed;
-                                }
-
-                                if input.is_right_pressed {
-                                    entity.rotation = entity.rotation
-                                        * cgmath::Quaternion::from_angle_z(cgmath::Deg(
-                                            -delta_angle,
-                                        ))
-                                }
-
-                                if input.is_left_pressed {
-                                    entity.rotation = entity.rotation
-                                        * cgmath::Quaternion::from_angle_z(cgmath::Deg(delta_angle))
-                                }
-                            }
+        let commands = &mut self.commands;
+        let entity_factory = &self.entity_factory;
+        let rng = &mut self.rng;
+
+        for (id, (position, rotation, control, physics, hyperspace, weapon, modifiers)) in
+            self.entities.query::<(
+                &WorldPosition,
+                &mut cgmath::Quaternion<f32>,
+                &Control,
+                &mut Physics,
+                Option<&Hyperspace>,
+                Option<&mut Weapon>,
+                Option<&Modifiers>,
+            )>()
+        {
+            if !control.enabled || matches!(hyperspace, Some(hyperspace) if hyperspace.is_jumping())
+            {
+                continue;
+            }
 
-                            {
-                                if control.weapon_cooldown < delta_time {
-                                    if input.is_backward_pressed {
-                                        to_spawn.push(self.entity_factory.make_laser(
-                                            position,
-                                            entity.rotation,
-                                            entity.physics.unwrap().linear_speed,
-                                        ));
-                                        control.weapon_cooldown = Duration::from_millis(200);
-                                    } else {
-                                        control.weapon_cooldown = Duration::ZERO
-                                    }
-                                } else {
-                                    control.weapon_cooldown -= delta_time;
-                                }
-                            }
-                        }
+            let rotation_speed = 180.;
+            let linear_acceleration = 50.;
+            {
+                let dtime = delta_time.as_secs_f32();
+                let delta_angle = dtime * rotation_speed;
+                let delta_linear_speed = dtime * linear_acceleration;
+
+                let direction = rotation.rotate_vector(cgmath::Vector3::unit_y()).truncate();
+
+                if input.is_forward_pressed {
+                    physics.linear_speed += direction * delta_linear_speed;
+                }
+
+                if input.is_right_pressed {
+                    *rotation =
+                        *rotation * cgmath::Quaternion::from_angle_z(cgmath::Deg(-delta_angle))
+                }
+
+                if input.is_left_pressed {
+                    *rotation =
+                        *rotation * cgmath::Quaternion::from_angle_z(cgmath::Deg(delta_angle))
+                }
+            }
+
+            if input.is_backward_pressed {
+                if let Some(shot) = weapon.and_then(|weapon| weapon.trigger(modifiers)) {
+                    for projectile in entity_factory.make_shot(
+                        &shot,
+                        id,
+                        *position,
+                        *rotation,
+                        physics.linear_speed,
+                        rng,
+                    ) {
+                        commands.spawn(projectile);
                     }
-                    _ => (),
                 }
             }
         }
 
-        to_spawn.into_iter().for_each(|entity| self.push(entity));
+        self
+    }
+
+    /// Weapons cool down and power-ups wear off
+    pub fn weapon_system(&mut self) -> &mut Self {
+        let dtime = self.delta_time();
+        for (_id, weapon) in self.entities.query::<&mut Weapon>() {
+            weapon.update(dtime);
+        }
+        for (id, modifiers) in self.entities.query::<&mut Modifiers>() {
+            modifiers.update(dtime);
+            if !modifiers.has_spread() && !modifiers.has_rapid_fire() {
+                self.commands.remove(id, ComponentKind::Modifiers);
+            }
+        }
 
         self
     }
@@ -350,10 +584,7 @@ impl GameState {
         let dtime = self.delta_time();
         self.entities
             .par_iter_mut()
-            .for_each(|option_entity| match option_entity {
-                Some(entity) => entity.update_physics(&dtime),
-                None => (),
-            });
+            .for_each(|entity| entity.update_physics(&dtime));
 
         self
     }
@@ -361,83 +592,388 @@ impl GameState {
     pub fn collision_system(&mut self) -> &mut Self {
         let shapes = self
             .entities
-            .par_iter()
-            .map(|option_entity| match option_entity {
-                Some(entity) => entity
-                    .shape
-                    .as_ref()
-                    .map(|shape| shape.translate(entity.position().to_vector2())),
-                None => None,
+            .iter_with_ids()
+            .filter(|(_id, entity)| !entity.is_in_hyperspace())
+            // Shapes without a collision layer don't touch anything
+            .filter_map(|(id, entity)| match (&entity.shape, entity.collision) {
+                (Some(shape), Some(collision)) => {
+                    let shape = shape
+                        .translate(entity.position().to_vector2())
+                        .rotate(entity.yaw());
+                    let filter = collision::Filter {
+                        layer: collision.layer.bit(),
+                        mask: collision.mask,
+                        owner: entity.owner,
+                        sensor: collision.sensor,
+                    };
+                    Some((id, shape, entity.motion(), filter))
+                }
+                _ => None,
             })
             .collect::<Vec<_>>();
 
-        for collision_group in collision::find_collisions(shapes) {
-            for this_id in &collision_group {
-                let other_ids = &collision_group
-                    .iter()
-                    .filter_map(|id| if id == this_id { None } else { Some(*id) })
-                    .collect::<Vec<_>>();
-
-                match self.get_entity(*this_id) {
-                    Some(this) => match this.collision {
-                        Some(collision) => {
-                            (collision.on_collision)(self, *this_id, other_ids.as_slice());
+        // An entity can be part of several collision groups.
+        // Merge them, so that each entity reacts to all of its contacts at once.
+        let mut contacts: BTreeMap<EntityId, Vec<EntityId>> = BTreeMap::new();
+        // Rigid bodies touching each other bounce off instead
+        let mut bounces = vec![];
+        // Sensors and whatever overlaps them, which only raise trigger events
+        let mut overlaps = BTreeSet::new();
+        for collision_group in collision::find_collisions(&shapes) {
+            let (first_id, other_ids) = match collision_group.split_first() {
+                Some((first_id, other_ids)) => (*first_id, other_ids),
+                None => continue,
+            };
+
+            for id in other_ids {
+                if self.is_sensor(first_id) || self.is_sensor(*id) {
+                    for (sensor_id, other_id) in [(first_id, *id), (*id, first_id)] {
+                        if self.is_sensor(sensor_id) {
+                            overlaps.insert((sensor_id, other_id));
                         }
-                        None => (),
-                    },
-                    None => (),
+                    }
+                    continue;
+                }
+                if self.is_rigid(first_id) && self.is_rigid(*id) {
+                    bounces.push((first_id, *id));
+                    continue;
                 }
+                contacts.entry(first_id).or_default().push(*id);
+                contacts.entry(*id).or_default().push(first_id);
+            }
+        }
+
+        let shapes = shapes
+            .into_iter()
+            .map(|(id, shape, _motion, _filter)| (id, shape))
+            .collect::<BTreeMap<_, _>>();
+        for (a_id, b_id) in bounces {
+            self.bounce(a_id, b_id, &shapes[&a_id], &shapes[&b_id]);
+        }
+
+        // Sensors react once to whatever enters them, unlike solid entities that react on every step of a contact
+        let mut entered: BTreeMap<EntityId, Vec<EntityId>> = BTreeMap::new();
+        let previous_overlaps = std::mem::replace(&mut self.overlaps, overlaps);
+        for (id, other_id) in self.overlaps.iter().copied() {
+            if previous_overlaps.contains(&(id, other_id)) {
+                self.events
+                    .publish(GameEvent::TriggerStayed { id, other_id });
+            } else {
+                self.events
+                    .publish(GameEvent::TriggerEntered { id, other_id });
+                entered.entry(id).or_default().push(other_id);
             }
         }
+        for (id, other_id) in previous_overlaps.difference(&self.overlaps).copied() {
+            self.events
+                .publish(GameEvent::TriggerExited { id, other_id });
+        }
+
+        for (this_id, other_ids) in contacts {
+            self.events.publish(GameEvent::Collided {
+                id: this_id,
+                other_ids: other_ids.clone(),
+            });
+            self.on_collision(this_id, &other_ids);
+        }
+        for (this_id, other_ids) in entered {
+            self.on_collision(this_id, &other_ids);
+        }
 
         self
     }
 
+    fn on_collision(&mut self, this_id: EntityId, other_ids: &[EntityId]) {
+        match self.get_entity(this_id) {
+            Some(this) => match this.collision {
+                Some(collision) => {
+                    (collision.on_collision)(self, this_id, other_ids);
+                }
+                None => (),
+            },
+            None => (),
+        }
+    }
+
+    fn is_sensor(&self, id: EntityId) -> bool {
+        matches!(self.get_entity(id), Some(Entity { collision: Some(collision), .. }) if collision.sensor)
+    }
+
+    fn is_rigid(&self, id: EntityId) -> bool {
+        matches!(self.get_entity(id), Some(Entity { physics: Some(physics), .. }) if physics.restitution.is_some())
+    }
+
+    /// Physical response to a contact between two rigid bodies, see `collision::bounce`
+    fn bounce(&mut self, a_id: EntityId, b_id: EntityId, a_shape: &Shape, b_shape: &Shape) {
+        let contact = match a_shape.contact(b_shape) {
+            Some(contact) => contact,
+            None => return,
+        };
+        let physics = |id| self.get_entity(id).and_then(|entity| entity.physics);
+        let (mut a, mut b) = match (physics(a_id), physics(b_id)) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return,
+        };
+
+        let radii = (a_shape.bounding_radius(), b_shape.bounding_radius());
+        let (a_correction, b_correction) = collision::bounce(&mut a, &mut b, &contact, radii);
+
+        for (id, physics, correction) in [(a_id, a, a_correction), (b_id, b, b_correction)] {
+            if let Some(entity) = self.get_entity_mut(id) {
+                entity.physics = Some(physics);
+                entity.translate(correction);
+            }
+        }
+    }
+
     pub fn lifetime_system(&mut self) -> &mut Self {
-        let mut to_kill = vec![];
         let dtime = self.delta_time();
-        for (id, option_entity) in self.entities.iter_mut().enumerate() {
-            if let Some(Entity {
-                lifetime: Some(ref mut lifetime),
-                ..
-            }) = option_entity
-            {
-                if lifetime.dies_after >= dtime {
-                    lifetime.dies_after -= dtime;
-                } else {
-                    to_kill.push(id);
+        for (id, lifetime) in self.entities.query::<&mut Lifetime>() {
+            if lifetime.dies_after >= dtime {
+                lifetime.dies_after -= dtime;
+            } else {
+                self.commands.despawn(id);
+            }
+        }
+
+        self
+    }
+
+    /// Controlled entities jump on Space, and reappear at a random position after a delay
+    pub fn hyperspace_system(&mut self, input: &Input) -> &mut Self {
+        let dtime = self.delta_time();
+        let mut arrivals = vec![];
+
+        for (id, (hyperspace, physics, control)) in
+            self.entities
+                .query::<(&mut Hyperspace, Option<&mut Physics>, Option<&Control>)>()
+        {
+            match hyperspace.reappears_in {
+                Some(left) if left > dtime => hyperspace.reappears_in = Some(left - dtime),
+                Some(_) => {
+                    hyperspace.reappears_in = None;
+                    hyperspace.ready_in = hyperspace.cooldown;
+                    arrivals.push((id, hyperspace.self_destruct_chance));
+                }
+                None => {
+                    hyperspace.ready_in = hyperspace.ready_in.saturating_sub(dtime);
+
+                    let wants_to_jump =
+                        matches!(control, Some(control) if control.enabled) && input.is_up_pressed;
+                    if wants_to_jump && hyperspace.is_ready() {
+                        hyperspace.reappears_in = Some(hyperspace.delay);
+                        if let Some(physics) = physics {
+                            physics.linear_speed = (0.0, 0.0).into();
+                        }
+                        self.events.publish(GameEvent::HyperspaceEntered { id });
+                    }
+                }
+            }
+        }
+
+        for (id, self_destruct_chance) in arrivals {
+            let (w, h) = self.world.size;
+            let position = self.world.new_position(
+                (
+                    self.rng.gen_range(-w / 2.0..w / 2.0),
+                    self.rng.gen_range(-h / 2.0..h / 2.0),
+                )
+                    .into(),
+            );
+            let destroyed = self.rng.gen_bool(self_destruct_chance);
+
+            let health = match self.get_entity_mut(id) {
+                Some(entity) => {
+                    entity.teleport(position);
+                    entity.health
+                }
+                None => None,
+            };
+            if let Some(health) = health {
+                if destroyed && !health.is_invulnerable() {
+                    // `lives_system` takes it from here
+                    self.commands
+                        .insert(id, Component::Health(Health { level: 0, ..health }));
                 }
             }
+            self.events.publish(GameEvent::HyperspaceExited {
+                id,
+                position,
+                destroyed,
+            });
         }
 
-        for id in to_kill {
-            self.kill(id);
+        self
+    }
+
+    /// Shields are up while LShift is held and there is energy left
+    pub fn shield_system(&mut self, input: &Input) -> &mut Self {
+        let dtime = self.delta_time();
+        for (_id, (shield, control, hyperspace)) in
+            self.entities
+                .query::<(&mut Shield, Option<&Control>, Option<&Hyperspace>)>()
+        {
+            let held = input.is_down_pressed
+                && matches!(control, Some(control) if control.enabled)
+                && !matches!(hyperspace, Some(hyperspace) if hyperspace.is_jumping());
+            shield.update(held, dtime);
         }
 
         self
     }
 
+    pub fn health_system(&mut self) -> &mut Self {
+        let dtime = self.delta_time();
+        for (_id, health) in self.entities.query::<&mut Health>() {
+            health.invulnerable_for = health.invulnerable_for.saturating_sub(dtime);
+            health.invincible_for = health.invincible_for.saturating_sub(dtime);
+        }
+
+        self
+    }
+
+    /// A destroyed ship costs a life,
+    /// and comes back at the world centre once there are no asteroids around
+    pub fn lives_system(&mut self) -> &mut Self {
+        let destroyed_ships = self
+            .entities_of_kind(Kind::is_ship)
+            .filter(|(_id, entity)| matches!(entity.health, Some(health) if health.level == 0))
+            .map(|(id, _entity)| id)
+            .collect::<Vec<_>>();
+
+        for id in destroyed_ships {
+            self.commands.despawn(id);
+            self.lives = self.lives.saturating_sub(1);
+            self.events
+                .publish(GameEvent::LifeLost { lives: self.lives });
+
+            if self.lives == 0 {
+                self.events
+                    .publish(GameEvent::GameOver { score: self.score });
+            } else {
+                self.respawn_in = Some(RESPAWN_DELAY);
+            }
+        }
+
+        if let Some(left) = self.respawn_in {
+            let dtime = self.delta_time();
+            if left > dtime {
+                self.respawn_in = Some(left - dtime);
+            } else if self.is_respawn_area_clear() {
+                self.respawn_in = None;
+                self.respawn_spaceship();
+            } else {
+                self.respawn_in = Some(Duration::ZERO);
+            }
+        }
+
+        self
+    }
+
+    fn is_respawn_area_clear(&self) -> bool {
+        let center = self.world.new_position((0.0, 0.0).into());
+        self.entities_of_kind(Kind::is_asteroid)
+            .all(|(_id, asteroid)| center.distance(&asteroid.position()) > RESPAWN_CLEAR_RADIUS)
+    }
+
+    fn respawn_spaceship(&mut self) {
+        let mut spaceship = self.entity_factory.make(
+            "Spaceship",
+            self.world.new_position((0.0, 0.0).into()),
+            cgmath::Quaternion::one(),
+            &mut self.rng,
+        );
+        if let Some(health) = &mut spaceship.health {
+            health.invulnerable_for = Health::INVULNERABILITY;
+        }
+        self.commands.spawn(spaceship);
+        self.events.publish(GameEvent::ShipRespawned);
+    }
+
     pub fn asteroids_count(&self) -> usize {
         self.entities
             .par_iter()
-            .filter_map(|entity_option| {
-                entity_option
-                    .as_ref()
-                    .filter(|entity| entity.name.starts_with("Asteroid"))
-            })
+            .filter(|entity| entity.is(Kind::is_asteroid))
             .count()
     }
 
-    pub fn asteroids_spawn_system(&mut self) -> &mut Self {
-        if self.asteroids_count() < 3 {
-            self.spawn_asteroid();
+    /// Sends a saucer every now and then, one at a time, while a level is on
+    pub fn saucer_spawn_system(&mut self) -> &mut Self {
+        let saucer_around = self.entities_of_kind(Kind::is_saucer).next().is_some();
+        if saucer_around || self.waves.in_breather() || self.spaceship().is_none() {
+            return self;
+        }
+
+        let dtime = self.delta_time();
+        if self.saucer_in > dtime {
+            self.saucer_in -= dtime;
+        } else {
+            self.saucer_in = SAUCER_INTERVAL;
+            self.spawn_saucer();
+        }
+
+        self
+    }
+
+    /// Saucers zigzag across the world and shoot at the ship when it's close enough
+    pub fn ai_system(&mut self) -> &mut Self {
+        let dtime = self.delta_time();
+        let target = self
+            .spaceship()
+            .filter(|(_id, ship)| !ship.is_in_hyperspace())
+            .map(|(_id, ship)| ship.position());
+        let commands = &mut self.commands;
+        let entity_factory = &self.entity_factory;
+        let rng = &mut self.rng;
+
+        for (id, (position, ai, physics, weapon)) in
+            self.entities
+                .query::<(&WorldPosition, &mut Ai, &mut Physics, Option<&mut Weapon>)>()
+        {
+            if ai.course_change_in > dtime {
+                ai.course_change_in -= dtime;
+            } else {
+                ai.course_change_in = ai.course_change_interval;
+                let deviation = cgmath::Deg(rng.gen_range(-45.0..45.0));
+                physics.linear_speed = cgmath::Basis2::from_angle(deviation)
+                    .rotate_vector(ai.heading)
+                    .normalize_to(ai.speed);
+            }
+
+            let (weapon, target) = match (weapon, target) {
+                (Some(weapon), Some(target)) if position.distance(&target) <= Ai::FIRE_RANGE => {
+                    (weapon, target)
+                }
+                _ => continue,
+            };
+            if let Some(shot) = weapon.trigger(None) {
+                let rotation = ai.aim(position, &target, rng);
+                for projectile in entity_factory.make_shot(
+                    &shot,
+                    id,
+                    *position,
+                    rotation,
+                    physics.linear_speed,
+                    rng,
+                ) {
+                    commands.spawn(projectile);
+                }
+            }
         }
 
         self
     }
 
-    pub fn submit(&mut self) {
-        self.last_update = Instant::now();
+    pub fn wave_system(&mut self) -> &mut Self {
+        let field_is_clear = self.asteroids_count() == 0;
+        if let Some(level) = self.waves.update(field_is_clear, self.delta_time()) {
+            for _ in 0..self.waves.asteroids() {
+                self.spawn_asteroid();
+            }
+            self.events.publish(GameEvent::LevelStarted { level });
+        }
+
+        self
     }
 }
 
@@ -445,9 +981,15 @@ impl GameState {
 fn test_gamestate_asteroids_count() {
     let world = World::init(1.0);
     let default_position = world.new_position((0.0, 0.0).into());
-    let a1 = Entity::new("Asteroid_1", default_position.clone());
-    let a2 = Entity::new("Asteroid_2", default_position.clone());
-    let s = Entity::new("Spaceship", default_position.clone());
+    let asteroid = Some(Kind::Asteroid {
+        size: components::AsteroidSize::Large,
+    });
+    let mut a1 = Entity::new("Asteroid_1", default_position.clone());
+    a1.kind = asteroid;
+    let mut a2 = Entity::new("Asteroid_2", default_position.clone());
+    a2.kind = asteroid;
+    let mut s = Entity::new("Spaceship", default_position.clone());
+    s.kind = Some(Kind::Ship);
 
     let entities = vec![
         Some(s.clone()),
@@ -457,16 +999,472 @@ fn test_gamestate_asteroids_count() {
         Some(a2.clone()),
     ];
 
-    let gamestate = GameState {
-        entities,
-        world,
-        last_update: Instant::now(),
-        score: 0,
-        entity_factory: EntityFactory::empty(),
-        cutscene_mode: false,
+    let mut gamestate = GameState::empty(1.0, test_prefabs(), false, 0);
+    gamestate.entities = entities.into();
+
+    assert_eq!(gamestate.asteroids_count(), 3);
+    assert_eq!(gamestate.entities_of_kind(Kind::is_ship).count(), 1);
+    assert_eq!(
+        gamestate.spaceship().map(|(_id, entity)| entity.name),
+        Some("Spaceship")
+    );
+}
+
+#[test]
+fn test_gamestate_advance_fixed_steps() {
+    // Only the step count and the leftover time are checked,
+    // whatever the systems spawn along the way (such as the first wave) doesn't matter
+    let mut gamestate = GameState::empty(1.0, test_prefabs(), false, 0);
+    let input = Input::new();
+
+    assert_eq!(gamestate.advance(FIXED_TIMESTEP / 2, &input), 0);
+    assert!((gamestate.interpolation_alpha() - 0.5).abs() < 1e-3);
+
+    assert_eq!(gamestate.advance(FIXED_TIMESTEP * 2, &input), 2);
+    assert!((gamestate.interpolation_alpha() - 0.5).abs() < 1e-3);
+
+    // Long stalls are clamped
+    assert_eq!(gamestate.advance(Duration::from_secs(10), &input), 30);
+}
+
+#[test]
+fn test_gamestate_collision_handled_once() {
+    let mut gamestate = GameState::empty(1.0, test_prefabs(), false, 0);
+
+    // Two lasers that don't touch each other hit the same asteroid,
+    // so the asteroid ends up in two collision groups
+    let laser_left = gamestate.entity_factory.make(
+        "Laser",
+        gamestate.world.new_position((-5.0, 0.0).into()),
+        cgmath::Quaternion::from_angle_z(cgmath::Deg(0.)),
+        &mut gamestate.rng,
+    );
+    let laser_right = gamestate.entity_factory.make(
+        "Laser",
+        gamestate.world.new_position((5.0, 0.0).into()),
+        cgmath::Quaternion::from_angle_z(cgmath::Deg(0.)),
+        &mut gamestate.rng,
+    );
+    let asteroid = gamestate.entity_factory.make(
+        "Asteroid_L",
+        gamestate.world.new_position((0.0, 0.0).into()),
+        cgmath::Quaternion::one(),
+        &mut gamestate.rng,
+    );
+    gamestate.push(laser_left);
+    gamestate.push(laser_right);
+    gamestate.push(asteroid);
+
+    gamestate.collision_system().apply_commands();
+
+    let names = gamestate
+        .entities
+        .iter()
+        .map(|entity| entity.name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["Asteroid_M", "Asteroid_M", "Cloud_L"]);
+    assert_eq!(gamestate.score(), 2);
+
+    let events = gamestate.drain_events();
+    let count = |f: fn(&GameEvent) -> bool| events.iter().filter(|event| f(event)).count();
+    assert_eq!(count(|e| matches!(e, GameEvent::Collided { .. })), 3);
+    assert_eq!(count(|e| matches!(e, GameEvent::EntityDestroyed { .. })), 3);
+    let last_score = events.iter().rev().find_map(|event| match event {
+        GameEvent::ScoreChanged { score } => Some(*score),
+        _ => None,
+    });
+    assert_eq!(last_score, Some(2));
+    assert_eq!(gamestate.events().count(), 0);
+}
+
+#[test]
+fn test_gamestate_split_inherits_velocity() {
+    let mut gamestate = GameState::empty(1.0, test_prefabs(), false, 0);
+
+    let mut asteroid = gamestate.entity_factory.make(
+        "Asteroid_L",
+        gamestate.world.new_position((0.0, 0.0).into()),
+        cgmath::Quaternion::one(),
+        &mut gamestate.rng,
+    );
+    let velocity = cgmath::vec2(10., 0.);
+    asteroid.physics.as_mut().unwrap().linear_speed = velocity;
+    gamestate.push(asteroid);
+
+    // Shot from the left, flying right faster than the asteroid
+    let laser = gamestate.entity_factory.make(
+        "Laser",
+        gamestate.world.new_position((-5.0, 0.0).into()),
+        cgmath::Quaternion::from_angle_z(cgmath::Deg(-90.)),
+        &mut gamestate.rng,
+    );
+    gamestate.push(laser);
+
+    gamestate.collision_system().apply_commands();
+
+    let parts = gamestate
+        .entities_of_kind(Kind::is_asteroid)
+        .map(|(_id, part)| (part.position(), part.physics.unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(parts.len(), 2);
+
+    // Each part keeps the asteroid's velocity, and is pushed apart from the other one
+    // across the hit direction, along y, by the split impulse over its own mass
+    let split_impulse = gamestate
+        .entity_factory
+        .prefabs
+        .get("Asteroid_L")
+        .split_impulse;
+    let (a, b) = (&parts[0], &parts[1]);
+    assert!(a.0.delta(&b.0).x.abs() < 1e-3);
+    for (_, physics) in &parts {
+        let push = physics.linear_speed - velocity;
+        assert!(push.x.abs() < 1e-3);
+        assert!((push.y.abs() - split_impulse / physics.mass).abs() < 1e-3);
+    }
+    assert!((a.1.linear_speed.y - velocity.y) * (b.1.linear_speed.y - velocity.y) < 0.);
+}
+
+#[test]
+fn test_gamestate_asteroids_bounce_across_the_edge() {
+    let mut gamestate = GameState::empty(1.0, test_prefabs(), false, 0);
+    let (w, _h) = gamestate.world.size;
+
+    // Touching across the right edge of the world, flying towards each other
+    let mut ids = vec![];
+    for (x, speed) in [(w / 2. - 2.5, 10.), (-w / 2. + 2.5, -10.)] {
+        let mut asteroid = gamestate.entity_factory.make(
+            "Asteroid_M",
+            gamestate.world.new_position((x, 0.).into()),
+            cgmath::Quaternion::one(),
+            &mut gamestate.rng,
+        );
+        asteroid.physics.as_mut().unwrap().linear_speed = (speed, 0.).into();
+        ids.push(gamestate.push(asteroid));
+    }
+
+    gamestate.collision_system().apply_commands();
+
+    let speed = |id| {
+        gamestate
+            .get_entity(id)
+            .unwrap()
+            .physics
+            .unwrap()
+            .linear_speed
     };
+    let (left, right) = (speed(ids[0]), speed(ids[1]));
+    assert!(left.x < 0. && right.x > 0.);
+    assert!((left + right).magnitude() < 1e-3);
+    assert!(right.x < 10.);
+
+    // Pushed apart, still on both sides of the edge
+    let position = |id| gamestate.get_entity(id).unwrap().position();
+    assert!(position(ids[0]).distance(&position(ids[1])) >= 6. - 1e-3);
+    assert!(position(ids[0]).to_vector2().x > 0.);
+}
 
+#[test]
+fn test_gamestate_waves() {
+    let mut gamestate = GameState::new_game(1.0, test_prefabs(), false, 0);
+    assert_eq!(gamestate.level(), 1);
     assert_eq!(gamestate.asteroids_count(), 3);
+
+    clear_asteroids(&mut gamestate);
+
+    let input = Input::new();
+    gamestate.step(&input);
+    assert!(gamestate.is_between_levels());
+    assert_eq!(gamestate.asteroids_count(), 0);
+
+    while gamestate.level() == 1 {
+        gamestate.step(&input);
+    }
+    assert!(!gamestate.is_between_levels());
+    assert_eq!(gamestate.asteroids_count(), 4);
+    assert!(gamestate
+        .events()
+        .any(|event| matches!(event, GameEvent::LevelStarted { level: 2 })));
+}
+
+#[test]
+fn test_gamestate_lives_and_respawn() {
+    let mut gamestate = GameState::new_game(1.0, test_prefabs(), false, 0);
+    let center = gamestate.world.new_position((0.0, 0.0).into());
+    clear_asteroids(&mut gamestate);
+
+    // Overlapping an asteroid for many steps costs a single health point
+    let asteroid = gamestate.entity_factory.make(
+        "Asteroid_L",
+        center,
+        cgmath::Quaternion::one(),
+        &mut gamestate.rng,
+    );
+    gamestate.push(asteroid);
+    let input = Input::new();
+    for _ in 0..10 {
+        gamestate.step(&input);
+    }
+    let health = gamestate.spaceship_health().unwrap();
+    assert_eq!(health.level, 2);
+    assert!(health.is_invulnerable());
+
+    let (ship_id, _ship) = gamestate.spaceship().unwrap();
+    gamestate.get_entity_mut(ship_id).unwrap().health = Some(Health::new(0));
+    gamestate.step(&input);
+    assert!(gamestate.spaceship().is_none());
+    assert_eq!(gamestate.lives(), 2);
+    assert!(!gamestate.is_over());
+
+    // No respawn while an asteroid is in the way
+    clear_asteroids(&mut gamestate);
+    let mut blocker = Entity::new("Asteroid_L", center);
+    blocker.kind = Some(Kind::Asteroid {
+        size: components::AsteroidSize::Large,
+    });
+    let blocker_id = gamestate.push(blocker);
+    for _ in 0..(RESPAWN_DELAY.as_secs() as usize * 2 * 120) {
+        gamestate.step(&input);
+    }
+    assert!(gamestate.spaceship().is_none());
+
+    gamestate.kill(blocker_id);
+    gamestate.step(&input);
+    let health = gamestate.spaceship_health().unwrap();
+    assert_eq!(health.level, 3);
+    assert!(health.is_invulnerable());
+}
+
+#[test]
+fn test_gamestate_blinked_out_ship_is_not_lit() {
+    let mut gamestate = GameState::new_game(1.0, test_prefabs(), false, 0);
+    clear_asteroids(&mut gamestate);
+    let lights = gamestate.light_uniforms().len();
+
+    let ship_id = gamestate.spaceship().unwrap().0;
+    let health = gamestate
+        .get_entity_mut(ship_id)
+        .unwrap()
+        .health
+        .as_mut()
+        .unwrap();
+    health.invulnerable_for = Duration::from_millis(150);
+    assert!(health.is_blinked_out());
+    assert!(gamestate.light_uniforms().len() < lights);
+}
+
+#[test]
+fn test_gamestate_hyperspace() {
+    let mut gamestate = GameState::new_game(1.0, test_prefabs(), false, 0);
+    let (ship_id, _ship) = gamestate.spaceship().unwrap();
+    let set_self_destruct_chance = |gamestate: &mut GameState, chance| {
+        let ship = gamestate.get_entity_mut(ship_id).unwrap();
+        let hyperspace = ship.hyperspace.as_mut().unwrap();
+        hyperspace.self_destruct_chance = chance;
+        hyperspace.ready_in = Duration::ZERO;
+    };
+    let steps_in_hyperspace = |gamestate: &mut GameState| {
+        let mut input = Input::new();
+        input.is_up_pressed = true;
+        gamestate.step(&input);
+        assert!(gamestate.get_entity(ship_id).unwrap().is_in_hyperspace());
+        let rendered = gamestate.entities_grouped();
+        assert!(!rendered
+            .values()
+            .flat_map(|mesh_map| mesh_map.values())
+            .flat_map(|material_map| material_map.values())
+            .flatten()
+            .any(|entity| entity.is_in_hyperspace()));
+
+        let mut steps = 1;
+        while matches!(gamestate.get_entity(ship_id), Some(ship) if ship.is_in_hyperspace()) {
+            gamestate.step(&input);
+            steps += 1;
+        }
+        steps
+    };
+
+    set_self_destruct_chance(&mut gamestate, 0.);
+    let time_away = FIXED_TIMESTEP * steps_in_hyperspace(&mut gamestate);
+    let hyperspace = gamestate.get_entity(ship_id).unwrap().hyperspace.unwrap();
+    assert!(time_away > hyperspace.delay && time_away <= hyperspace.delay + FIXED_TIMESTEP * 2);
+    assert!(!hyperspace.is_ready());
+    assert!(gamestate.events().any(|event| matches!(
+        event,
+        GameEvent::HyperspaceExited {
+            destroyed: false,
+            ..
+        }
+    )));
+
+    set_self_destruct_chance(&mut gamestate, 1.);
+    steps_in_hyperspace(&mut gamestate);
+    assert!(gamestate.get_entity(ship_id).is_none());
+    assert_eq!(gamestate.lives(), 2);
+}
+
+#[test]
+fn test_gamestate_shield() {
+    let mut gamestate = GameState::new_game(1.0, test_prefabs(), false, 0);
+    clear_asteroids(&mut gamestate);
+
+    let mut asteroid = gamestate.entity_factory.make(
+        "Asteroid_L",
+        gamestate.world.new_position((8.0, 0.0).into()),
+        cgmath::Quaternion::one(),
+        &mut gamestate.rng,
+    );
+    asteroid.physics.as_mut().unwrap().linear_speed = (-10.0, 0.0).into();
+    let asteroid_id = gamestate.push(asteroid);
+
+    let mut input = Input::new();
+    input.is_down_pressed = true;
+    for _ in 0..10 {
+        gamestate.step(&input);
+    }
+
+    // The asteroid bounced off without breaking or hurting the ship
+    let asteroid = gamestate.get_entity(asteroid_id).unwrap();
+    assert!(asteroid.physics.unwrap().linear_speed.x > 0.);
+    assert_eq!(gamestate.spaceship_health().unwrap().level, 3);
+    let shield = gamestate.spaceship().unwrap().1.shield.unwrap();
+    assert!(shield.active);
+    assert!(shield.charge() < 1.);
+    assert!(shield.light().is_some());
+
+    gamestate.step(&Input::new());
+    let drained = gamestate.spaceship().unwrap().1.shield.unwrap();
+    assert!(!drained.active);
+    gamestate.step(&Input::new());
+    let recharged = gamestate.spaceship().unwrap().1.shield.unwrap();
+    assert!(recharged.energy > drained.energy);
+}
+
+#[test]
+fn test_gamestate_power_ups() {
+    let mut gamestate = GameState::new_game(1.0, test_prefabs(), false, 0);
+    clear_asteroids(&mut gamestate);
+
+    let center = gamestate.world.new_position((0.0, 0.0).into());
+    for name in ["PowerUp_Spread", "PowerUp_Invincibility"] {
+        let power_up = gamestate.entity_factory.make(
+            name,
+            center,
+            cgmath::Quaternion::one(),
+            &mut gamestate.rng,
+        );
+        gamestate.push(power_up);
+    }
+
+    let mut input = Input::new();
+    input.is_backward_pressed = true;
+    gamestate.step(&input);
+
+    let is_power_up = |kind| matches!(kind, Kind::PowerUp { .. });
+    assert_eq!(gamestate.entities_of_kind(is_power_up).count(), 0);
+    let (_id, ship) = gamestate.spaceship().unwrap();
+    assert!(ship.modifiers.unwrap().has_spread());
+    assert!(ship.health.unwrap().is_invulnerable());
+    assert!(!ship.health.unwrap().is_blinked_out());
+
+    // The ship fired before picking the power-up, so the spread shows from the next shot on
+    let lasers = gamestate
+        .entities_of_kind(Kind::is_projectile)
+        .map(|(id, _entity)| id)
+        .collect::<Vec<_>>();
+    for id in lasers {
+        gamestate.kill(id);
+    }
+    while gamestate.entities_of_kind(Kind::is_projectile).count() == 0 {
+        gamestate.step(&input);
+    }
+    assert_eq!(gamestate.entities_of_kind(Kind::is_projectile).count(), 3);
+
+    // Keep the next wave away, so that nothing else hurts the ship
+    let steps =
+        (Modifiers::DURATION.as_secs_f32() / FIXED_TIMESTEP.as_secs_f32()).ceil() as usize + 1;
+    for _ in 0..steps {
+        gamestate.step(&Input::new());
+        clear_asteroids(&mut gamestate);
+    }
+    let (_id, ship) = gamestate.spaceship().unwrap();
+    assert!(ship.modifiers.is_none());
+    assert!(!ship.health.unwrap().is_invulnerable());
+}
+
+#[test]
+fn test_gamestate_triggers() {
+    let mut gamestate = GameState::new_game(1.0, test_prefabs(), false, 0);
+    clear_asteroids(&mut gamestate);
+    let (ship_id, _ship) = gamestate.spaceship().unwrap();
+
+    // A power-up that is never picked up stays around like a zone would
+    let mut zone = gamestate.entity_factory.make(
+        "PowerUp_Health",
+        gamestate.world.new_position((0.0, 0.0).into()),
+        cgmath::Quaternion::one(),
+        &mut gamestate.rng,
+    );
+    zone.collision.as_mut().unwrap().on_collision = |_gamestate, _this_id, _other_ids| ();
+    let zone_id = gamestate.push(zone);
+
+    fn triggers(gamestate: &mut GameState) -> Vec<(&'static str, EntityId, EntityId)> {
+        gamestate.collision_system().apply_commands();
+        gamestate
+            .drain_events()
+            .into_iter()
+            .filter_map(|event| match event {
+                GameEvent::TriggerEntered { id, other_id } => Some(("entered", id, other_id)),
+                GameEvent::TriggerStayed { id, other_id } => Some(("stayed", id, other_id)),
+                GameEvent::TriggerExited { id, other_id } => Some(("exited", id, other_id)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    assert_eq!(triggers(&mut gamestate), [("entered", zone_id, ship_id)]);
+    assert_eq!(triggers(&mut gamestate), [("stayed", zone_id, ship_id)]);
+    gamestate.kill(ship_id);
+    assert_eq!(triggers(&mut gamestate), [("exited", zone_id, ship_id)]);
+    assert_eq!(triggers(&mut gamestate), []);
+}
+
+#[test]
+fn test_gamestate_saucer_aims_across_the_edge() {
+    let mut gamestate = GameState::new_game(1.0, test_prefabs(), false, 0);
+    clear_asteroids(&mut gamestate);
+
+    let (w, _h) = gamestate.world.size;
+    let ship_id = gamestate.spaceship().unwrap().0;
+    let ship_position = gamestate.world.new_position((-w / 2. + 5., 0.).into());
+    gamestate
+        .get_entity_mut(ship_id)
+        .unwrap()
+        .teleport(ship_position);
+
+    let mut saucer = gamestate.entity_factory.make(
+        "Saucer_S",
+        gamestate.world.new_position((w / 2. - 5., 0.).into()),
+        cgmath::Quaternion::one(),
+        &mut gamestate.rng,
+    );
+    saucer.ai.as_mut().unwrap().aim_error = 0.;
+    let saucer_id = gamestate.push(saucer);
+
+    gamestate.ai_system().apply_commands();
+
+    // The ship is closer across the right edge than across the whole world
+    let (laser_id, laser) = gamestate
+        .entities_of_kind(Kind::is_projectile)
+        .next()
+        .unwrap();
+    assert_eq!(laser.owner, Some(saucer_id));
+    assert!(laser.physics.unwrap().linear_speed.x > 0.);
+
+    // The laser starts inside the saucer, but doesn't destroy it
+    gamestate.collision_system().apply_commands();
+    assert!(gamestate.get_entity(saucer_id).is_some());
+    assert!(gamestate.get_entity(laser_id).is_some());
 }
 
 #[test]
@@ -486,14 +1484,8 @@ fn test_gamestate_entities_grouped_by_name() {
         Some(a.clone()),
     ];
 
-    let gamestate = GameState {
-        entities,
-        world,
-        last_update: Instant::now(),
-        score: 0,
-        entity_factory: EntityFactory::empty(),
-        cutscene_mode: false,
-    };
+    let mut gamestate = GameState::empty(1.0, test_prefabs(), false, 0);
+    gamestate.entities = entities.into();
 
     let expected = vec![
         ("A", vec![a.clone(), a.clone()]),
@@ -529,3 +1521,21 @@ where
 {
     BTreeMap::from_iter(btreemap.iter().map(|(k, v)| (*k, f(v))))
 }
+
+#[cfg(test)]
+pub(crate) fn test_prefabs() -> Rc<Prefabs> {
+    let catalogue = crate::assets::AssetCatalogue::load().unwrap();
+    Rc::new(Prefabs::load(&catalogue).unwrap())
+}
+
+/// Removes every asteroid, so that nothing gets in the way of what a test looks at
+#[cfg(test)]
+fn clear_asteroids(gamestate: &mut GameState) {
+    let asteroids = gamestate
+        .entities_of_kind(Kind::is_asteroid)
+        .map(|(id, _entity)| id)
+        .collect::<Vec<_>>();
+    for id in asteroids {
+        gamestate.kill(id);
+    }
+}
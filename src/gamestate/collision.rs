@@ -1,28 +1,265 @@
-use super::geometry::Shape;
+use cgmath::InnerSpace;
+use std::collections::BTreeSet;
 
-pub(crate) fn find_collisions(shapes: Vec<Option<Shape>>) -> Vec<Vec<usize>> {
-    let mut total_collisions = vec![];
+use super::components::Physics;
+use super::geometry::{Contact, Shape};
 
-    // @TODO: use a faster collision detection algorithm
-    for (i, shape) in shapes.iter().enumerate().filter_map(to_option) {
-        let mut this_shape_collisions = vec![i];
+/// Share of the normal impulse that tangential friction can reach
+const FRICTION: f32 = 0.3;
 
-        for (j, another_shape) in shapes.iter().enumerate().skip(i + 1).filter_map(to_option) {
-            if Shape::overlaps(shape, another_shape) {
-                this_shape_collisions.push(j);
+/// Side of a broadphase grid cell, in world units. Cells are stretched a little so that they tile the world.
+const CELL_SIZE: f32 = 10.;
+
+/// Which other shapes a shape can touch at all, checked before its geometry
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Filter<Id> {
+    /// Bit of the shape's collision layer
+    pub layer: u32,
+    /// Bits of the layers it touches. Both shapes of a pair have to touch each other's layer.
+    pub mask: u32,
+    /// Shapes never touch their owner, like projectiles the ship that fired them
+    pub owner: Option<Id>,
+    /// Sensors don't stop fast shapes, which also hit whatever they reach after passing through one
+    pub sensor: bool,
+}
+
+impl<Id: Copy + PartialEq> Filter<Id> {
+    fn allows(&self, id: Id, another: &Filter<Id>, another_id: Id) -> bool {
+        self.mask & another.layer != 0
+            && another.mask & self.layer != 0
+            && self.owner != Some(another_id)
+            && another.owner != Some(id)
+    }
+}
+
+impl<Id> Default for Filter<Id> {
+    /// Touches everything
+    fn default() -> Self {
+        Self {
+            layer: !0,
+            mask: !0,
+            owner: None,
+            sensor: false,
+        }
+    }
+}
+
+/// Groups of ids whose shapes touch during a simulation step. The first id in a group touches all the others.
+/// Shapes are given at the end of the step, along with their motion over it, and are swept along that motion,
+/// so that fast shapes don't tunnel through small ones.
+/// A shape that moves further than its own size only hits whatever it reaches first, sensors aside.
+pub(crate) fn find_collisions<Id: Copy + PartialEq>(
+    shapes: &[(Id, Shape, cgmath::Vector2<f32>, Filter<Id>)],
+) -> Vec<Vec<Id>> {
+    let impacts = find_impacts(shapes);
+
+    let mut earliest_impact = vec![None; shapes.len()];
+    for (i, j, time) in &impacts {
+        if shapes[*i].3.sensor || shapes[*j].3.sensor {
+            continue;
+        }
+        for k in [*i, *j] {
+            let (_id, shape, motion, _filter) = &shapes[k];
+            if motion.magnitude() > shape.bounding_radius() {
+                earliest_impact[k] = Some(time.min(earliest_impact[k].unwrap_or(*time)));
+            }
+        }
+    }
+    let is_hit = |k: usize, time: f32| match earliest_impact[k] {
+        Some(earliest) => time <= earliest,
+        None => true,
+    };
+
+    let mut hits = vec![vec![]; shapes.len()];
+    for (i, j, time) in impacts {
+        if is_hit(i, time) && is_hit(j, time) {
+            hits[i].push(shapes[j].0);
+        }
+    }
+
+    shapes
+        .iter()
+        .zip(hits)
+        .filter(|(_shape, hits)| !hits.is_empty())
+        .map(|((id, _shape, _motion, _filter), hits)| {
+            let mut this_shape_collisions = vec![*id];
+            this_shape_collisions.extend(hits);
+            this_shape_collisions
+        })
+        .collect()
+}
+
+/// Pairs of indices `i < j` of shapes that touch during the step, with their time of impact, sorted by indices.
+/// Only shapes sharing a cell of a grid laid over the world, and allowed to touch by their filters,
+/// are tested against each other.
+/// The grid wraps around like the world does, so cells at one edge neighbour cells at the opposite one.
+fn find_impacts<Id: Copy + PartialEq>(
+    shapes: &[(Id, Shape, cgmath::Vector2<f32>, Filter<Id>)],
+) -> Vec<(usize, usize, f32)> {
+    let world = match shapes.first() {
+        Some((_id, shape, _motion, _filter)) => shape.origin().world_rect(),
+        None => return vec![],
+    };
+    let (left, top) = world.left_top;
+    let (right, bottom) = world.right_bottom;
+    let columns = ((right - left) / CELL_SIZE).ceil().max(1.) as usize;
+    let rows = ((top - bottom) / CELL_SIZE).ceil().max(1.) as usize;
+    let cell_width = (right - left) / columns as f32;
+    let cell_height = (top - bottom) / rows as f32;
+
+    // Cells covered by a range of coordinates, wrapped around the world
+    let cells = |from: f32, to: f32, origin: f32, size: f32, count: usize| {
+        let first = ((from - origin) / size).floor() as i64;
+        let last = ((to - origin) / size).floor() as i64;
+        let span = ((last - first + 1) as usize).min(count);
+        (0..span).map(move |k| (first + k as i64).rem_euclid(count as i64) as usize)
+    };
+
+    let mut grid = vec![vec![]; columns * rows];
+    for (i, (_id, shape, motion, _filter)) in shapes.iter().enumerate() {
+        // Box around the shape's sweep over the step
+        let end = shape.origin().to_vector2();
+        let start = end - motion;
+        let radius = shape.bounding_radius();
+        let (min_x, max_x) = (end.x.min(start.x) - radius, end.x.max(start.x) + radius);
+        let (min_y, max_y) = (end.y.min(start.y) - radius, end.y.max(start.y) + radius);
+
+        for column in cells(min_x, max_x, left, cell_width, columns) {
+            for row in cells(min_y, max_y, bottom, cell_height, rows) {
+                grid[row * columns + column].push(i);
             }
         }
+    }
 
-        if this_shape_collisions.len() > 1 {
-            total_collisions.push(this_shape_collisions);
+    let mut candidates = BTreeSet::new();
+    for cell in &grid {
+        for (k, i) in cell.iter().enumerate() {
+            for j in &cell[k + 1..] {
+                candidates.insert((*i, *j));
+            }
         }
     }
 
-    total_collisions
+    candidates
+        .into_iter()
+        .filter(|(i, j)| {
+            let (id, _, _, filter) = &shapes[*i];
+            let (another_id, _, _, another_filter) = &shapes[*j];
+            filter.allows(*id, another_filter, *another_id)
+        })
+        .filter_map(|(i, j)| {
+            let (_, shape, motion, _) = &shapes[i];
+            let (_, another_shape, another_motion, _) = &shapes[j];
+            time_of_impact(shape, *motion, another_shape, *another_motion).map(|time| (i, j, time))
+        })
+        .collect()
 }
 
-fn to_option<T>(t: (usize, &Option<T>)) -> Option<(usize, &T)> {
-    t.1.as_ref().map(|v| (t.0, v))
+/// Earliest moment of the step, from 0 to 1, when two shapes moving by their `motion` touch.
+/// Shapes are given at the end of the step.
+pub(crate) fn time_of_impact(
+    shape: &Shape,
+    motion: cgmath::Vector2<f32>,
+    another_shape: &Shape,
+    another_motion: cgmath::Vector2<f32>,
+) -> Option<f32> {
+    let relative_motion = another_motion - motion;
+    let bounding_time = swept_circles(
+        shape.origin().delta(&another_shape.origin()),
+        relative_motion,
+        shape.bounding_radius() + another_shape.bounding_radius(),
+    );
+
+    match (shape, another_shape) {
+        (Shape::Circle { .. }, Shape::Circle { .. }) => bounding_time,
+        _ => {
+            // Other shapes are tested along the sweep, a little more often than it takes to pass through the thinner one,
+            // starting from when their bounding circles meet
+            let start = bounding_time?;
+            let step = 0.5 * shape.thickness().min(another_shape.thickness()).max(0.01);
+            let samples = ((1. - start) * relative_motion.magnitude() / step).ceil() as usize;
+            (0..=samples).find_map(|k| {
+                let time = if samples == 0 {
+                    start
+                } else {
+                    start + (1. - start) * k as f32 / samples as f32
+                };
+                let shape = shape.translate(-motion * (1. - time));
+                let another_shape = another_shape.translate(-another_motion * (1. - time));
+                shape.contact(&another_shape).map(|_| time)
+            })
+        }
+    }
+}
+
+/// A point moving by `relative_motion` and ending at `offset` from a circle's centre,
+/// against that circle of `radius`
+fn swept_circles(
+    offset: cgmath::Vector2<f32>,
+    relative_motion: cgmath::Vector2<f32>,
+    radius: f32,
+) -> Option<f32> {
+    let start = offset - relative_motion;
+    let c = start.magnitude2() - radius * radius;
+    if c < 0. {
+        return Some(0.);
+    }
+
+    let a = relative_motion.magnitude2();
+    let b = 2. * start.dot(relative_motion);
+    let discriminant = b * b - 4. * a * c;
+    if a == 0. || discriminant < 0. {
+        return None;
+    }
+
+    let time = (-b - discriminant.sqrt()) / (2. * a);
+    if (0.0..=1.0).contains(&time) {
+        Some(time)
+    } else {
+        None
+    }
+}
+
+/// Impulses that make two rigid bodies touching along `contact` bounce off each other.
+/// Returns how far each body has to move to stop overlapping.
+pub(crate) fn bounce(
+    a: &mut Physics,
+    b: &mut Physics,
+    contact: &Contact,
+    radii: (f32, f32),
+) -> (cgmath::Vector2<f32>, cgmath::Vector2<f32>) {
+    let inverse_mass = 1. / a.mass + 1. / b.mass;
+    let normal = contact.normal;
+
+    // Heavier bodies move less
+    let separation = normal * contact.depth / inverse_mass;
+    let corrections = (-separation / a.mass, separation / b.mass);
+
+    let relative_speed = b.linear_speed - a.linear_speed;
+    let approach_speed = relative_speed.dot(normal);
+    if approach_speed >= 0. {
+        return corrections;
+    }
+
+    let restitution = a.restitution.unwrap_or(0.).min(b.restitution.unwrap_or(0.));
+    let j = -(1. + restitution) * approach_speed / inverse_mass;
+    let impulse = normal * j;
+
+    // Sliding contacts rub the bodies into spinning
+    let tangent_speed = relative_speed - normal * approach_speed;
+    let friction = if tangent_speed.magnitude2() > 0. {
+        let tangent = tangent_speed.normalize();
+        let jt = (-relative_speed.dot(tangent) / inverse_mass).max(-FRICTION * j);
+        tangent * jt
+    } else {
+        cgmath::vec2(0., 0.)
+    };
+
+    let (offset_a, offset_b) = contact.offsets;
+    a.apply_impulse(-(impulse + friction), offset_a, radii.0);
+    b.apply_impulse(impulse + friction, offset_b, radii.1);
+
+    corrections
 }
 
 #[test]
@@ -31,72 +268,289 @@ fn test_find_collisions() {
 
     let empty: Vec<Vec<usize>> = vec![];
 
+    fn find_collisions<Id: Copy + PartialEq>(shapes: &[(Id, Shape)]) -> Vec<Vec<Id>> {
+        let still = shapes
+            .iter()
+            .map(|(id, shape)| (*id, shape.clone(), cgmath::vec2(0., 0.), Filter::default()))
+            .collect::<Vec<_>>();
+        crate::gamestate::collision::find_collisions(&still)
+    }
+
     fn origin(v: (f32, f32)) -> WorldPosition {
         let world = World::init(1.0);
         world.new_position(v.into())
     }
 
-    assert_eq!(find_collisions(vec![]), empty);
+    assert_eq!(find_collisions::<usize>(&[]), empty);
     assert_eq!(
-        find_collisions(vec![
-            Some(Shape::Circle {
-                origin: origin((0.0, 0.0)),
-                radius: 20.
-            }),
-            Some(Shape::Circle {
-                origin: origin((40.0, 0.0)),
-                radius: 10.
-            })
+        find_collisions(&[
+            (
+                0,
+                Shape::Circle {
+                    origin: origin((0.0, 0.0)),
+                    radius: 20.
+                }
+            ),
+            (
+                1,
+                Shape::Circle {
+                    origin: origin((40.0, 0.0)),
+                    radius: 10.
+                }
+            )
         ]),
         empty
     );
     assert_eq!(
-        find_collisions(vec![
-            Some(Shape::Circle {
-                origin: origin((0.0, 0.0)),
-                radius: 20.
-            }),
-            Some(Shape::Circle {
-                origin: origin((40.0, 0.0)),
-                radius: 10.
-            }),
-            Some(Shape::Circle {
-                origin: origin((-20.0, 0.0)),
-                radius: 20.
-            })
+        find_collisions(&[
+            (
+                0,
+                Shape::Circle {
+                    origin: origin((0.0, 0.0)),
+                    radius: 20.
+                }
+            ),
+            (
+                1,
+                Shape::Circle {
+                    origin: origin((40.0, 0.0)),
+                    radius: 10.
+                }
+            ),
+            (
+                2,
+                Shape::Circle {
+                    origin: origin((-20.0, 0.0)),
+                    radius: 20.
+                }
+            )
         ]),
         vec![vec![0_usize, 2_usize]]
     );
     assert_eq!(
-        find_collisions(vec![
-            None,
-            Some(Shape::Circle {
-                origin: origin((0.0, 0.0)),
-                radius: 20.
-            }),
-            Some(Shape::Circle {
-                origin: origin((40.0, 0.0)),
-                radius: 10.
-            }),
-            Some(Shape::Circle {
-                origin: origin((-20.0, 0.0)),
-                radius: 20.
-            })
+        find_collisions(&[
+            (
+                1,
+                Shape::Circle {
+                    origin: origin((0.0, 0.0)),
+                    radius: 20.
+                }
+            ),
+            (
+                2,
+                Shape::Circle {
+                    origin: origin((40.0, 0.0)),
+                    radius: 10.
+                }
+            ),
+            (
+                3,
+                Shape::Circle {
+                    origin: origin((-20.0, 0.0)),
+                    radius: 20.
+                }
+            )
         ]),
         vec![vec![1_usize, 3_usize]]
     );
     assert_eq!(
-        find_collisions(vec![
-            None,
-            Some(Shape::Circle {
-                origin: origin((0.0, -40.0)),
-                radius: 15.
-            }),
-            Some(Shape::Circle {
-                origin: origin((0.0, 40.0)),
-                radius: 15.
-            }),
+        find_collisions(&[
+            (
+                1,
+                Shape::Circle {
+                    origin: origin((0.0, -40.0)),
+                    radius: 15.
+                }
+            ),
+            (
+                2,
+                Shape::Circle {
+                    origin: origin((0.0, 40.0)),
+                    radius: 15.
+                }
+            )
         ]),
         vec![vec![1_usize, 2_usize]]
     );
+
+    // Overlapping shapes that filter each other out
+    let circle = Shape::Circle {
+        origin: origin((0.0, 0.0)),
+        radius: 1.,
+    };
+    let filtered = |filter: Filter<usize>, another_filter: Filter<usize>| {
+        let still = cgmath::vec2(0., 0.);
+        crate::gamestate::collision::find_collisions(&[
+            (1, circle.clone(), still, filter),
+            (2, circle.clone(), still, another_filter),
+        ])
+    };
+    let layer = |layer: u32, mask: u32| Filter {
+        layer,
+        mask,
+        owner: None,
+        sensor: false,
+    };
+    assert_eq!(filtered(layer(1, 2), layer(2, 1)), vec![vec![1, 2]]);
+    assert_eq!(filtered(layer(1, 2), layer(2, 2)), empty);
+    assert_eq!(filtered(layer(1, 1), layer(1, 1)), vec![vec![1, 2]]);
+    let owned = Filter {
+        owner: Some(1),
+        ..Filter::default()
+    };
+    assert_eq!(filtered(Filter::default(), owned), empty);
+}
+
+#[test]
+fn test_find_collisions_swept() {
+    use crate::gamestate::world::World;
+
+    fn find_collisions(shapes: &[(usize, Shape, cgmath::Vector2<f32>)]) -> Vec<Vec<usize>> {
+        let shapes = shapes
+            .iter()
+            .map(|(id, shape, motion)| (*id, shape.clone(), *motion, Filter::default()))
+            .collect::<Vec<_>>();
+        crate::gamestate::collision::find_collisions(&shapes)
+    }
+
+    let world = World::init(1.0);
+    let (w, _h) = world.size;
+    let circle = |x: f32, radius: f32| Shape::Circle {
+        origin: world.new_position((x, 0.0).into()),
+        radius,
+    };
+
+    // A laser that flew past a small asteroid during the step still hits it
+    let laser = (0, circle(10., 1.), cgmath::vec2(20., 0.));
+    let asteroid = (1, circle(0., 1.), cgmath::vec2(0., 0.));
+    assert_eq!(
+        find_collisions(&[laser, asteroid.clone()]),
+        vec![vec![0, 1]]
+    );
+    assert_eq!(
+        find_collisions(&[(0, circle(10., 1.), cgmath::vec2(5., 0.)), asteroid]),
+        Vec::<Vec<usize>>::new()
+    );
+
+    // Across the world's edge
+    let laser = (0, circle(-w / 2. + 5., 1.), cgmath::vec2(10., 0.));
+    let asteroid = (1, circle(w / 2. - 1., 1.), cgmath::vec2(0., 0.));
+    assert_eq!(find_collisions(&[laser, asteroid]), vec![vec![0, 1]]);
+
+    // Only the first of two asteroids in the way is hit
+    let laser = (0, circle(10., 1.), cgmath::vec2(30., 0.));
+    let near = (1, circle(-10., 1.), cgmath::vec2(0., 0.));
+    let far = (2, circle(0., 1.), cgmath::vec2(0., 0.));
+    assert_eq!(
+        find_collisions(&[laser.clone(), far.clone(), near.clone()]),
+        vec![vec![0, 1]]
+    );
+    let toi = time_of_impact(&laser.1, laser.2, &near.1, near.2).unwrap();
+    assert!((toi - 8. / 30.).abs() < 1e-4);
+
+    // Unless the first one is a sensor
+    let mut shapes = [laser.clone(), far, near]
+        .map(|(id, shape, motion)| (id, shape, motion, Filter::default()));
+    shapes[2].3.sensor = true;
+    assert_eq!(
+        crate::gamestate::collision::find_collisions(&shapes),
+        vec![vec![0, 2, 1]]
+    );
+
+    // A thin capsule flying past a turned square hits its corner, but not when the square is upright
+    let capsule = Shape::Capsule {
+        origin: world.new_position((10., 1.3).into()),
+        rotation: std::f32::consts::FRAC_PI_2,
+        half_length: 1.,
+        radius: 0.1,
+    };
+    let square = |rotation: f32| Shape::Polygon {
+        origin: world.new_position((0., 0.).into()),
+        rotation,
+        points: vec![(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)]
+            .into_iter()
+            .map(cgmath::Vector2::from)
+            .collect(),
+    };
+    let motion = cgmath::vec2(20., 0.);
+    assert!(time_of_impact(&capsule, motion, &square(0.), cgmath::vec2(0., 0.)).is_none());
+    let toi = time_of_impact(
+        &capsule,
+        motion,
+        &square(std::f32::consts::FRAC_PI_4),
+        cgmath::vec2(0., 0.),
+    )
+    .unwrap();
+    assert!(toi > 0. && toi < 0.5);
+}
+
+#[test]
+fn test_find_impacts_matches_brute_force() {
+    use crate::gamestate::world::World;
+    use rand::{Rng, SeedableRng};
+
+    let world = World::init(1.0);
+    let (w, h) = world.size;
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+
+    // Plenty of shapes near the edges and fast ones, so that many pairs touch across the edges.
+    // Every third shape is on a layer that only touches itself.
+    let shapes = (0..300)
+        .map(|i| {
+            let position = (
+                rng.gen_range(-w / 2.0..w / 2.),
+                rng.gen_range(-h / 2.0..h / 2.),
+            );
+            let shape = Shape::Circle {
+                origin: world.new_position(position.into()),
+                radius: rng.gen_range(0.5..6.),
+            };
+            let motion = if i % 10 == 0 {
+                cgmath::vec2(rng.gen_range(-40.0..40.), rng.gen_range(-40.0..40.))
+            } else {
+                cgmath::vec2(0., 0.)
+            };
+            let filter = if i % 3 == 0 {
+                Filter {
+                    layer: 1,
+                    mask: 1,
+                    owner: None,
+                    sensor: false,
+                }
+            } else {
+                Filter {
+                    layer: 2,
+                    mask: 2,
+                    owner: None,
+                    sensor: false,
+                }
+            };
+            (i, shape, motion, filter)
+        })
+        .collect::<Vec<_>>();
+
+    let mut brute_force = vec![];
+    for i in 0..shapes.len() {
+        for j in i + 1..shapes.len() {
+            let (_, shape, motion, filter) = &shapes[i];
+            let (_, another_shape, another_motion, another_filter) = &shapes[j];
+            if filter.layer != another_filter.layer {
+                continue;
+            }
+            if let Some(time) = time_of_impact(shape, *motion, another_shape, *another_motion) {
+                brute_force.push((i, j, time));
+            }
+        }
+    }
+
+    let impacts = find_impacts(&shapes);
+    let across_edge = impacts
+        .iter()
+        .filter(|(i, j, _)| {
+            let (a, b) = (shapes[*i].1.origin(), shapes[*j].1.origin());
+            a.delta(&b) != b.to_vector2() - a.to_vector2()
+        })
+        .count();
+    assert!(across_edge > 0);
+    assert_eq!(impacts, brute_force);
 }
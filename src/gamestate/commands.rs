@@ -0,0 +1,142 @@
+use super::components::{
+    Ai, Collision, Control, Health, Hyperspace, Lifetime, Light, Modifiers, Physics, Renderable,
+    Shield, Weapon,
+};
+use super::entity::Entity;
+use super::geometry::Shape;
+use super::EntityId;
+
+/// A component value, to be attached to an entity by a command
+pub enum Component {
+    Shape(Shape),
+    Renderable(Renderable),
+    Physics(Physics),
+    Collision(Collision),
+    Control(Control),
+    Health(Health),
+    Hyperspace(Hyperspace),
+    Ai(Ai),
+    Modifiers(Modifiers),
+    Weapon(Weapon),
+    Shield(Shield),
+    Lifetime(Lifetime),
+    Light(Light),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComponentKind {
+    Shape,
+    Renderable,
+    Physics,
+    Collision,
+    Control,
+    Health,
+    Hyperspace,
+    Ai,
+    Modifiers,
+    Weapon,
+    Shield,
+    Lifetime,
+    Light,
+}
+
+pub enum Command {
+    Spawn(Box<Entity>),
+    Despawn(EntityId),
+    Insert(EntityId, Component),
+    Remove(EntityId, ComponentKind),
+    AddScore(usize),
+}
+
+/// Changes to the world requested by systems and collision callbacks.
+/// They are not visible until `GameState::apply_commands`, so every system
+/// sees the world in the same state it had when the system started.
+#[derive(Default)]
+pub struct Commands {
+    queue: Vec<Command>,
+}
+
+impl Commands {
+    pub fn spawn(&mut self, entity: Entity) {
+        self.queue.push(Command::Spawn(Box::new(entity)));
+    }
+
+    pub fn despawn(&mut self, id: EntityId) {
+        self.queue.push(Command::Despawn(id));
+    }
+
+    pub fn insert(&mut self, id: EntityId, component: Component) {
+        self.queue.push(Command::Insert(id, component));
+    }
+
+    pub fn remove(&mut self, id: EntityId, kind: ComponentKind) {
+        self.queue.push(Command::Remove(id, kind));
+    }
+
+    pub fn add_score(&mut self, points: usize) {
+        self.queue.push(Command::AddScore(points));
+    }
+
+    pub fn drain(&mut self) -> Vec<Command> {
+        std::mem::take(&mut self.queue)
+    }
+}
+
+impl Component {
+    pub fn kind(&self) -> ComponentKind {
+        match self {
+            Component::Shape(_) => ComponentKind::Shape,
+            Component::Renderable(_) => ComponentKind::Renderable,
+            Component::Physics(_) => ComponentKind::Physics,
+            Component::Collision(_) => ComponentKind::Collision,
+            Component::Control(_) => ComponentKind::Control,
+            Component::Health(_) => ComponentKind::Health,
+            Component::Hyperspace(_) => ComponentKind::Hyperspace,
+            Component::Ai(_) => ComponentKind::Ai,
+            Component::Modifiers(_) => ComponentKind::Modifiers,
+            Component::Weapon(_) => ComponentKind::Weapon,
+            Component::Shield(_) => ComponentKind::Shield,
+            Component::Lifetime(_) => ComponentKind::Lifetime,
+            Component::Light(_) => ComponentKind::Light,
+        }
+    }
+}
+
+impl Entity {
+    pub fn insert(&mut self, component: Component) {
+        match component {
+            Component::Shape(shape) => self.shape = Some(shape),
+            Component::Renderable(renderable) => self.renderable = Some(renderable),
+            Component::Physics(physics) => self.physics = Some(physics),
+            Component::Collision(collision) => self.collision = Some(collision),
+            Component::Control(control) => self.control = Some(control),
+            Component::Health(health) => self.health = Some(health),
+            Component::Hyperspace(hyperspace) => self.hyperspace = Some(hyperspace),
+            Component::Ai(ai) => self.ai = Some(ai),
+            Component::Modifiers(modifiers) => self.modifiers = Some(modifiers),
+            Component::Weapon(weapon) => self.weapon = Some(weapon),
+            Component::Shield(shield) => self.shield = Some(shield),
+            Component::Lifetime(lifetime) => self.lifetime = Some(lifetime),
+            Component::Light(light) => self.light = Some(light),
+        }
+    }
+
+    /// Takes the component of `kind` away, if the entity has one
+    pub fn remove(&mut self, kind: ComponentKind) -> Option<Component> {
+        match kind {
+            ComponentKind::Shape => self.shape.take().map(Component::Shape),
+            ComponentKind::Renderable => self.renderable.take().map(Component::Renderable),
+            ComponentKind::Physics => self.physics.take().map(Component::Physics),
+            ComponentKind::Collision => self.collision.take().map(Component::Collision),
+            ComponentKind::Control => self.control.take().map(Component::Control),
+            ComponentKind::Health => self.health.take().map(Component::Health),
+            ComponentKind::Hyperspace => self.hyperspace.take().map(Component::Hyperspace),
+            ComponentKind::Ai => self.ai.take().map(Component::Ai),
+            ComponentKind::Modifiers => self.modifiers.take().map(Component::Modifiers),
+            ComponentKind::Weapon => self.weapon.take().map(Component::Weapon),
+            ComponentKind::Shield => self.shield.take().map(Component::Shield),
+            ComponentKind::Lifetime => self.lifetime.take().map(Component::Lifetime),
+            ComponentKind::Light => self.light.take().map(Component::Light),
+        }
+    }
+}
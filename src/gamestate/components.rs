@@ -1,44 +1,234 @@
 use std::time::Duration;
 
-use cgmath::{Deg, Rotation3, Zero};
+use cgmath::{Deg, InnerSpace, One, Rotation3, Zero};
 use rand::Rng;
+use serde::Deserialize;
 use shared::LightUniform;
 
 use crate::shaders::ShaderName;
 
-use super::GameState;
+use super::world::WorldPosition;
+use super::{EntityId, GameState};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum AsteroidSize {
+    Small,
+    Medium,
+    Large,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum SaucerSize {
+    Small,
+    Large,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum PowerUpEffect {
+    /// Fire three lasers at once
+    Spread,
+    RapidFire,
+    ExtraHealth,
+    Invincibility,
+}
+
+/// What an entity is in terms of game rules, independent of its mesh or name
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum Kind {
+    Ship,
+    Asteroid { size: AsteroidSize },
+    Saucer { size: SaucerSize },
+    Projectile,
+    PowerUp { effect: PowerUpEffect },
+    Effect,
+}
+
+impl Kind {
+    pub fn is_ship(self) -> bool {
+        self == Kind::Ship
+    }
+
+    pub fn is_asteroid(self) -> bool {
+        matches!(self, Kind::Asteroid { .. })
+    }
+
+    pub fn is_saucer(self) -> bool {
+        matches!(self, Kind::Saucer { .. })
+    }
+
+    pub fn is_projectile(self) -> bool {
+        self == Kind::Projectile
+    }
+
+    /// Score for shooting an entity of this kind down
+    pub fn points(self) -> usize {
+        match self {
+            Kind::Asteroid { .. } => 1,
+            Kind::Saucer {
+                size: SaucerSize::Large,
+            } => 5,
+            Kind::Saucer {
+                size: SaucerSize::Small,
+            } => 10,
+            _ => 0,
+        }
+    }
+}
+
+/// Group of entities that collide alike, see `res/collision_layers.ron` for which groups touch
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum CollisionLayer {
+    Ship,
+    Asteroid,
+    Saucer,
+    Projectile,
+    EnemyProjectile,
+    PowerUp,
+}
+
+impl CollisionLayer {
+    pub fn bit(self) -> u32 {
+        1 << self as u32
+    }
+}
 
 #[derive(Clone, Copy)]
 pub struct Collision {
-    pub on_collision: fn(&mut GameState, this_id: usize, other_ids: &[usize]),
+    pub on_collision: fn(&mut GameState, this_id: EntityId, other_ids: &[EntityId]),
+    pub layer: CollisionLayer,
+    /// Bits of the layers this one touches
+    pub mask: u32,
+    /// Sensors only notice what overlaps them, without bouncing or hurting anything.
+    /// `on_collision` is called with whatever has just started to overlap, see `GameEvent::TriggerEntered`.
+    pub sensor: bool,
 }
 
 #[derive(Clone, Copy)]
 pub struct Control {
     pub enabled: bool,
-    pub weapon_cooldown: Duration,
 }
 
 impl Control {
     pub fn enabled() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// Anything that shoots projectiles: the ship, saucers, and whatever comes next
+#[derive(Clone, Copy)]
+pub struct Weapon {
+    /// Prefab of the projectiles
+    pub projectile: &'static str,
+    pub fire_interval: Duration,
+    /// Projectiles per shot, fanned out `spread_angle` degrees apart
+    pub spread_count: usize,
+    pub spread_angle: f32,
+    /// Distance in front of the shooter where projectiles appear
+    pub muzzle_offset: f32,
+    /// Shots left, unlimited if `None`
+    pub ammo: Option<usize>,
+    /// Heat added by every shot, as a share of the maximum heat
+    pub heat_per_shot: f32,
+    /// Heat lost per second
+    pub cooling: f32,
+    /// From 0 to 1
+    pub heat: f32,
+    /// Set when the heat reaches 1, the weapon doesn't fire until it fully cools down
+    pub overheated: bool,
+    pub cooldown: Duration,
+}
+
+/// Projectiles fired by one pull of the trigger
+pub struct Shot {
+    pub projectile: &'static str,
+    pub count: usize,
+    pub spread_angle: f32,
+    pub muzzle_offset: f32,
+}
+
+impl Weapon {
+    pub fn new(projectile: &'static str, fire_interval: Duration) -> Self {
         Self {
-            enabled: true,
-            weapon_cooldown: Duration::ZERO,
+            projectile,
+            fire_interval,
+            spread_count: 1,
+            spread_angle: 0.,
+            muzzle_offset: 0.,
+            ammo: None,
+            heat_per_shot: 0.,
+            cooling: 0.,
+            heat: 0.,
+            overheated: false,
+            cooldown: Duration::ZERO,
         }
     }
+
+    pub fn update(&mut self, dtime: Duration) {
+        self.cooldown = self.cooldown.saturating_sub(dtime);
+        self.heat = (self.heat - self.cooling * dtime.as_secs_f32()).max(0.);
+        if self.heat == 0. {
+            self.overheated = false;
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.cooldown.is_zero() && self.ammo != Some(0) && !self.overheated
+    }
+
+    /// Spends ammo, heats the weapon up and starts the cooldown.
+    /// Power-ups in `modifiers` change the shot and the fire rate.
+    pub fn trigger(&mut self, modifiers: Option<&Modifiers>) -> Option<Shot> {
+        if !self.is_ready() {
+            return None;
+        }
+
+        let mut shot = Shot {
+            projectile: self.projectile,
+            count: self.spread_count,
+            spread_angle: self.spread_angle,
+            muzzle_offset: self.muzzle_offset,
+        };
+        self.cooldown = self.fire_interval;
+
+        if let Some(modifiers) = modifiers {
+            if modifiers.has_spread() && shot.count < Modifiers::SPREAD_COUNT {
+                shot.count = Modifiers::SPREAD_COUNT;
+                shot.spread_angle = Modifiers::SPREAD_ANGLE;
+            }
+            if modifiers.has_rapid_fire() {
+                self.cooldown = self.fire_interval.mul_f32(Modifiers::RAPID_FIRE_FACTOR);
+            }
+        }
+
+        if let Some(ammo) = &mut self.ammo {
+            *ammo -= 1;
+        }
+        self.heat = (self.heat + self.heat_per_shot).min(1.);
+        if self.heat >= 1. {
+            self.overheated = true;
+        }
+
+        Some(shot)
+    }
 }
 
 #[derive(Clone, Copy)]
 pub struct Physics {
     pub linear_speed: cgmath::Vector2<f32>,
     pub max_linear_speed: f32,
-    pub angular_speed: cgmath::Quaternion<f32>,
+    /// Rotation axis, scaled by the rotation speed in radians per second
+    pub angular_velocity: cgmath::Vector3<f32>,
+    /// Share of the linear speed lost per second, so that entities drift to a halt
+    pub linear_damping: f32,
+    /// Share of the angular speed lost per second
+    pub angular_damping: f32,
+    pub mass: f32,
+    /// Rigid bodies bounce off each other instead of colliding, keeping this share of their approach speed
+    pub restitution: Option<f32>,
 }
 
 impl Physics {
-    pub fn random(max_linear_speed: f32, max_angular_speed: f32) -> Self {
-        let mut rng = rand::thread_rng();
-
+    pub fn random(rng: &mut impl Rng, max_linear_speed: f32, max_angular_speed: f32) -> Self {
         let linear_speed = cgmath::Vector2 {
             x: rng.gen_range(-max_linear_speed..max_linear_speed),
             y: rng.gen_range(-max_linear_speed..max_linear_speed),
@@ -49,15 +239,52 @@ impl Physics {
             y: rng.gen_range(0.0..1.0),
             z: rng.gen_range(0.0..1.0),
         };
-        let angle = Deg(rng.gen_range(0.0..max_angular_speed));
-        let angular_speed = cgmath::Quaternion::from_axis_angle(axis, angle);
-
-        let max_linear_speed = 30.;
+        let angle = cgmath::Rad::from(Deg(rng.gen_range(0.0..max_angular_speed)));
+        let axis = if axis.magnitude2() > 0. {
+            axis.normalize()
+        } else {
+            cgmath::Vector3::unit_z()
+        };
 
         Self {
             linear_speed,
-            angular_speed,
-            max_linear_speed,
+            angular_velocity: axis * angle.0,
+            ..Default::default()
+        }
+    }
+
+    /// Rotation over `dtime`, exact for any time step
+    pub fn rotation_over(&self, dtime: f32) -> cgmath::Quaternion<f32> {
+        let speed = self.angular_velocity.magnitude();
+        if speed > 0. {
+            cgmath::Quaternion::from_axis_angle(
+                self.angular_velocity / speed,
+                cgmath::Rad(speed * dtime),
+            )
+        } else {
+            cgmath::Quaternion::one()
+        }
+    }
+
+    /// Exponential decay, so that damping doesn't depend on the time step
+    pub fn damp(&mut self, dtime: f32) {
+        self.linear_speed *= (-self.linear_damping * dtime).exp();
+        self.angular_velocity *= (-self.angular_damping * dtime).exp();
+    }
+
+    /// Push a disc of `radius` at `offset` from its centre. Off-centre impulses make it spin.
+    pub fn apply_impulse(
+        &mut self,
+        impulse: cgmath::Vector2<f32>,
+        offset: cgmath::Vector2<f32>,
+        radius: f32,
+    ) {
+        self.linear_speed += impulse / self.mass;
+
+        let inertia = 0.5 * self.mass * radius * radius;
+        let torque = offset.x * impulse.y - offset.y * impulse.x;
+        if inertia > 0. {
+            self.angular_velocity.z += torque / inertia;
         }
     }
 }
@@ -67,7 +294,11 @@ impl Default for Physics {
         Self {
             linear_speed: (0.0, 0.0).into(),
             max_linear_speed: 30.,
-            angular_speed: cgmath::Quaternion::zero(),
+            angular_velocity: cgmath::Vector3::zero(),
+            linear_damping: 0.,
+            angular_damping: 0.,
+            mass: 1.,
+            restitution: None,
         }
     }
 }
@@ -76,14 +307,201 @@ impl Default for Physics {
 pub struct Health {
     pub level: usize,
     pub invincible: bool,
+    /// Time left until damage can be dealt again
+    pub invulnerable_for: Duration,
+    /// Timed invincibility from a power-up. Unlike `invulnerable_for`, it doesn't make the entity blink.
+    pub invincible_for: Duration,
 }
 
 impl Health {
+    /// How long an entity can't be hurt again after a hit
+    pub const INVULNERABILITY: Duration = Duration::from_secs(2);
+    const BLINK_PERIOD: Duration = Duration::from_millis(100);
+
+    pub fn new(level: usize) -> Self {
+        Self {
+            level,
+            invincible: false,
+            invulnerable_for: Duration::ZERO,
+            invincible_for: Duration::ZERO,
+        }
+    }
+
+    pub fn is_invulnerable(&self) -> bool {
+        self.invincible || !self.invulnerable_for.is_zero() || !self.invincible_for.is_zero()
+    }
+
+    /// Damage starts an invulnerability window,
+    /// so that a single contact lasting several steps counts as one hit
     pub fn deal_damage(&mut self, damage: usize) {
-        if self.invincible {
+        if damage == 0 || self.is_invulnerable() {
             return;
         }
-        self.level = (self.level as isize - damage as isize).max(0) as usize;
+        self.level = self.level.saturating_sub(damage);
+        self.invulnerable_for = Self::INVULNERABILITY;
+    }
+
+    /// Entities blink while they are invulnerable after a hit
+    pub fn is_blinked_out(&self) -> bool {
+        let periods = self.invulnerable_for.as_millis() / Self::BLINK_PERIOD.as_millis();
+        periods % 2 == 1
+    }
+}
+
+/// Timed power-up effects, see `Weapon::trigger`
+#[derive(Clone, Copy, Default)]
+pub struct Modifiers {
+    pub spread_for: Duration,
+    pub rapid_fire_for: Duration,
+}
+
+impl Modifiers {
+    /// How long a power-up lasts once picked up
+    pub const DURATION: Duration = Duration::from_secs(10);
+    const SPREAD_COUNT: usize = 3;
+    /// Degrees
+    const SPREAD_ANGLE: f32 = 15.;
+    /// Share of the usual fire interval
+    const RAPID_FIRE_FACTOR: f32 = 0.4;
+
+    pub fn update(&mut self, dtime: Duration) {
+        self.spread_for = self.spread_for.saturating_sub(dtime);
+        self.rapid_fire_for = self.rapid_fire_for.saturating_sub(dtime);
+    }
+
+    pub fn has_spread(&self) -> bool {
+        !self.spread_for.is_zero()
+    }
+
+    pub fn has_rapid_fire(&self) -> bool {
+        !self.rapid_fire_for.is_zero()
+    }
+}
+
+/// Energy shield: while it's up, asteroids bounce off instead of dealing damage
+#[derive(Clone, Copy)]
+pub struct Shield {
+    pub energy: f32,
+    pub max_energy: f32,
+    /// Energy spent per second while the shield is up
+    pub drain: f32,
+    /// Energy restored per second while the shield is down
+    pub recharge: f32,
+    pub active: bool,
+    /// Seconds since the shield went up, drives the light pulse
+    pub pulse: f32,
+}
+
+impl Shield {
+    /// Share of `max_energy` needed to raise the shield, so that it doesn't flicker when drained
+    const MIN_ENERGY_TO_RAISE: f32 = 0.2;
+
+    pub fn new(max_energy: f32, drain: f32, recharge: f32) -> Self {
+        Self {
+            energy: max_energy,
+            max_energy,
+            drain,
+            recharge,
+            active: false,
+            pulse: 0.,
+        }
+    }
+
+    pub fn update(&mut self, held: bool, dtime: Duration) {
+        let dtime = dtime.as_secs_f32();
+
+        self.active = if self.active {
+            held && self.energy > 0.
+        } else {
+            held && self.energy >= self.max_energy * Self::MIN_ENERGY_TO_RAISE
+        };
+
+        if self.active {
+            self.energy = (self.energy - self.drain * dtime).max(0.);
+            self.pulse += dtime;
+        } else {
+            self.energy = (self.energy + self.recharge * dtime).min(self.max_energy);
+            self.pulse = 0.;
+        }
+    }
+
+    /// From 0 to 1
+    pub fn charge(&self) -> f32 {
+        self.energy / self.max_energy
+    }
+
+    /// Pulsing glow around the entity while the shield is up
+    pub fn light(&self) -> Option<Light> {
+        if self.active {
+            let pulse = (self.pulse * std::f32::consts::TAU * 2.).sin();
+            Some(Light {
+                color: [0.3, 0.8, 1.],
+                radius: 20. + 5. * pulse,
+                z: 10.,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// Flying saucer behaviour: cross the world, change course now and then, and shoot at the ship
+#[derive(Clone, Copy)]
+pub struct Ai {
+    pub speed: f32,
+    pub course_change_interval: Duration,
+    /// Maximum aiming error, in degrees
+    pub aim_error: f32,
+    /// General direction of the crossing, course changes deviate from it
+    pub heading: cgmath::Vector2<f32>,
+    pub course_change_in: Duration,
+}
+
+impl Ai {
+    /// Saucers don't shoot at the ship from across the world
+    pub const FIRE_RANGE: f32 = 60.;
+
+    /// Rotation that points a projectile from `position` towards `target`, give or take `aim_error`.
+    /// Aims along the shortest path, which can go across the world's edge.
+    pub fn aim(
+        &self,
+        position: &WorldPosition,
+        target: &WorldPosition,
+        rng: &mut impl Rng,
+    ) -> cgmath::Quaternion<f32> {
+        let direction = position.delta(target);
+        // Projectiles fly along their local Y axis
+        let angle = Deg::from(cgmath::Rad(f32::atan2(-direction.x, direction.y)));
+        let error = if self.aim_error > 0. {
+            Deg(rng.gen_range(-self.aim_error..self.aim_error))
+        } else {
+            Deg(0.)
+        };
+
+        cgmath::Quaternion::from_angle_z(angle + error)
+    }
+}
+
+/// Jump to a random place in the world, at the risk of not coming back
+#[derive(Clone, Copy)]
+pub struct Hyperspace {
+    /// How long the entity stays away
+    pub delay: Duration,
+    pub cooldown: Duration,
+    /// Probability of being destroyed on arrival, from 0 to 1
+    pub self_destruct_chance: f64,
+    /// Set while the entity is in hyperspace
+    pub reappears_in: Option<Duration>,
+    pub ready_in: Duration,
+}
+
+impl Hyperspace {
+    pub fn is_jumping(&self) -> bool {
+        self.reappears_in.is_some()
+    }
+
+    pub fn is_ready(&self) -> bool {
+        !self.is_jumping() && self.ready_in.is_zero()
     }
 }
 
@@ -92,7 +510,8 @@ pub struct Lifetime {
     pub dies_after: Duration,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Light {
     pub color: [f32; 3],
     pub radius: f32,
@@ -110,4 +529,66 @@ pub struct Renderable {
     pub shader: ShaderName,
     pub mesh: usize,
     pub material: usize,
+    /// Size relative to the mesh
+    pub scale: f32,
+}
+
+#[test]
+fn test_weapon_ammo_and_heat() {
+    let dtime = Duration::from_millis(100);
+    let mut weapon = Weapon {
+        ammo: Some(3),
+        heat_per_shot: 0.6,
+        cooling: 1.,
+        ..Weapon::new("Laser", dtime)
+    };
+
+    assert_eq!(weapon.trigger(None).unwrap().count, 1);
+    assert!(weapon.trigger(None).is_none());
+    weapon.update(dtime);
+    assert!(weapon.trigger(None).is_some());
+    assert!(weapon.overheated);
+
+    // Doesn't fire again until it has fully cooled down
+    weapon.update(dtime * 5);
+    assert!(!weapon.is_ready());
+    weapon.update(dtime * 5);
+    assert!(weapon.is_ready());
+
+    let modifiers = Modifiers {
+        spread_for: Modifiers::DURATION,
+        rapid_fire_for: Modifiers::DURATION,
+    };
+    assert_eq!(weapon.trigger(Some(&modifiers)).unwrap().count, 3);
+    assert!(weapon.cooldown < dtime);
+    assert_eq!(weapon.ammo, Some(0));
+    weapon.update(dtime);
+    assert!(weapon.trigger(None).is_none());
+}
+
+#[test]
+fn test_physics_integration_is_step_independent() {
+    let mut coarse = Physics {
+        linear_speed: (10., 0.).into(),
+        angular_velocity: (0., 0., std::f32::consts::PI).into(),
+        linear_damping: 0.5,
+        ..Default::default()
+    };
+    let mut fine = coarse;
+
+    let coarse_rotation = coarse.rotation_over(1.);
+    coarse.damp(1.);
+
+    let mut fine_rotation = cgmath::Quaternion::one();
+    for _ in 0..120 {
+        fine_rotation = fine.rotation_over(1. / 120.) * fine_rotation;
+        fine.damp(1. / 120.);
+    }
+
+    // Half a turn a second, whatever the step
+    let half_turn = cgmath::Quaternion::from_angle_z(Deg(180.));
+    assert!((coarse_rotation - half_turn).magnitude() < 1e-4);
+    assert!((fine_rotation - half_turn).magnitude() < 1e-4);
+    assert!((coarse.linear_speed - fine.linear_speed).magnitude() < 1e-3);
+    assert!(coarse.linear_speed.x < 10.);
 }
@@ -1,21 +1,32 @@
-use super::components::{self, Collision, Control, Health, Lifetime, Light, Physics, Renderable};
-use super::geometry::{self, Shape};
+use super::commands::Component;
+use super::components::{
+    self, Collision, Control, Health, Kind, Modifiers, Physics, PowerUpEffect, Shot,
+};
+use super::events::GameEvent;
+use super::geometry;
+use super::prefab::{CollisionBehaviour, Prefabs};
+use super::query::EntityParts;
 use super::world::WorldPosition;
+use super::{EntityId, GameRng, GameState};
 
 use crate::instance::Instance;
-use crate::resource::Resources;
-use crate::shaders::ShaderName;
-use cgmath::{prelude::*, Deg};
+use cgmath::prelude::*;
 use cgmath::{InnerSpace, Zero};
 use core::fmt::Debug;
+use rand::Rng;
 use std::rc::Rc;
 use std::time::Duration;
 
 #[derive(Clone)]
 pub struct Entity {
     pub name: &'static str,
+    pub kind: Option<components::Kind>,
+    /// Entity that spawned this one, like the shooter of a projectile. They never collide.
+    pub owner: Option<EntityId>,
     pub rotation: cgmath::Quaternion<f32>,
     position: WorldPosition,
+    /// Position and rotation at the beginning of the current simulation step, used for interpolation
+    previous_transform: Option<(WorldPosition, cgmath::Quaternion<f32>)>,
     entered_world: bool, // @TODO: find a way to set it whenever position changes
     pub shape: Option<geometry::Shape>,
     pub renderable: Option<components::Renderable>,
@@ -23,6 +34,11 @@ pub struct Entity {
     pub collision: Option<components::Collision>,
     pub control: Option<components::Control>,
     pub health: Option<components::Health>,
+    pub hyperspace: Option<components::Hyperspace>,
+    pub ai: Option<components::Ai>,
+    pub modifiers: Option<components::Modifiers>,
+    pub weapon: Option<components::Weapon>,
+    pub shield: Option<components::Shield>,
     pub lifetime: Option<components::Lifetime>,
     pub light: Option<components::Light>,
 }
@@ -31,7 +47,10 @@ impl Default for Entity {
     fn default() -> Self {
         Self {
             name: "",
+            kind: None,
+            owner: None,
             position: WorldPosition::default(),
+            previous_transform: None,
             rotation: cgmath::Quaternion::zero(),
             // @TODO: reconsider if asteroids enter the world by default.
             // Reason: when asteroid breaks into smaller parts at the world's border, those parts can fly away into space before they enter the world and therefore stay unreachable forever.
@@ -42,6 +61,11 @@ impl Default for Entity {
             collision: None,
             control: None,
             health: None,
+            hyperspace: None,
+            ai: None,
+            modifiers: None,
+            weapon: None,
+            shield: None,
             lifetime: None,
             light: None,
         }
@@ -64,23 +88,101 @@ impl Entity {
         }
     }
 
-    pub fn to_instance(&self) -> Instance {
-        Instance {
-            position: self.position.to_vector3(),
-            rotation: self.rotation,
+    /// Instance in between the previous and the current simulation step.
+    /// `alpha` of 0 gives the previous state, 1 gives the current one.
+    pub fn to_instance(&self, alpha: f32) -> Instance {
+        let scale = self
+            .renderable
+            .as_ref()
+            .map(|renderable| renderable.scale)
+            .unwrap_or(1.0);
+        match self.previous_transform {
+            Some((previous_position, previous_rotation)) => Instance {
+                position: previous_position.lerp(&self.position, alpha).to_vector3(),
+                rotation: previous_rotation.nlerp(self.rotation, alpha),
+                scale,
+            },
+            None => Instance {
+                position: self.position.to_vector3(),
+                rotation: self.rotation,
+                scale,
+            },
         }
     }
 
+    pub fn remember_transform(&mut self) {
+        self.previous_transform = Some((self.position, self.rotation));
+    }
+
     pub fn position(&self) -> WorldPosition {
         self.position
     }
 
+    /// Angle around the Z axis in radians, counter-clockwise from facing up
+    pub fn yaw(&self) -> f32 {
+        let forward = self.rotation.rotate_vector(cgmath::Vector3::unit_y());
+        (-forward.x).atan2(forward.y)
+    }
+
+    /// Split into separately borrowable fields for queries
+    pub fn parts(&mut self) -> EntityParts<'_> {
+        EntityParts {
+            position: &self.position,
+            rotation: Some(&mut self.rotation),
+            kind: self.kind.as_mut(),
+            shape: self.shape.as_mut(),
+            renderable: self.renderable.as_mut(),
+            physics: self.physics.as_mut(),
+            collision: self.collision.as_mut(),
+            control: self.control.as_mut(),
+            health: self.health.as_mut(),
+            hyperspace: self.hyperspace.as_mut(),
+            ai: self.ai.as_mut(),
+            modifiers: self.modifiers.as_mut(),
+            weapon: self.weapon.as_mut(),
+            shield: self.shield.as_mut(),
+            lifetime: self.lifetime.as_mut(),
+            light: self.light.as_mut(),
+        }
+    }
+
+    /// Entities in hyperspace are neither visible nor collide with anything
+    pub fn is_in_hyperspace(&self) -> bool {
+        matches!(self.hyperspace, Some(hyperspace) if hyperspace.is_jumping())
+    }
+
+    /// Hidden entities are neither drawn nor lit
+    pub fn is_hidden(&self) -> bool {
+        matches!(self.health, Some(health) if health.is_blinked_out()) || self.is_in_hyperspace()
+    }
+
+    /// Displacement since the beginning of the current simulation step, along the shortest path
+    pub fn motion(&self) -> cgmath::Vector2<f32> {
+        match self.previous_transform {
+            Some((previous_position, _)) => previous_position.delta(&self.position),
+            None => cgmath::Vector2::zero(),
+        }
+    }
+
+    /// Move instantly, without interpolating from the previous position
+    pub fn teleport(&mut self, position: WorldPosition) {
+        self.position = position;
+        self.previous_transform = None;
+    }
+
+    pub fn is(&self, kind_matches: fn(Kind) -> bool) -> bool {
+        self.kind.map(kind_matches).unwrap_or(false)
+    }
+
     pub fn entered_world(&self) -> bool {
         self.entered_world
     }
 
     pub fn update_physics(&mut self, dtime: &Duration) {
-        let speeds = if let Some(ref mut physics) = self.physics {
+        let dtime = dtime.as_secs_f32();
+        let motion = if let Some(ref mut physics) = self.physics {
+            physics.damp(dtime);
+
             // Limit maximum speed
             if physics.linear_speed.magnitude2() > 0. {
                 let new_magnitude = physics
@@ -89,25 +191,18 @@ impl Entity {
                 physics.linear_speed = physics.linear_speed.normalize_to(new_magnitude);
             }
 
-            Some((physics.linear_speed, physics.angular_speed))
+            Some((physics.linear_speed * dtime, physics.rotation_over(dtime)))
         } else {
             None
         };
 
-        if let Some((linear_speed, angular_speed)) = speeds {
-            // Move
-            self.translate(linear_speed * (dtime.as_millis() as f32) / 1000.0);
-
-            // Rotate
-            self.rotation = cgmath::Quaternion::nlerp(
-                self.rotation,
-                self.rotation * angular_speed,
-                (dtime.as_millis() as f32) / 1000.0,
-            );
+        if let Some((translation, rotation)) = motion {
+            self.translate(translation);
+            self.rotation = (rotation * self.rotation).normalize();
         }
     }
 
-    fn translate(&mut self, v: cgmath::Vector2<f32>) {
+    pub fn translate(&mut self, v: cgmath::Vector2<f32>) {
         self.position = if self.entered_world {
             self.position.translate(v)
         } else {
@@ -115,16 +210,13 @@ impl Entity {
         };
 
         self.entered_world = self.entered_world
-            || if let Some(shape) = self.shape {
-                match shape {
-                    Shape::Circle { origin, radius } => {
-                        let rect = self.position.world_rect();
-                        let center = origin
-                            .translate_unsafe(self.position.to_vector2())
-                            .to_tuple();
-                        rect.contains_circle(center, radius)
-                    }
-                }
+            || if let Some(shape) = &self.shape {
+                let rect = self.position.world_rect();
+                let center = shape
+                    .origin()
+                    .translate_unsafe(self.position.to_vector2())
+                    .to_tuple();
+                rect.contains_circle(center, shape.bounding_radius())
             } else {
                 // Shapeless entities always fit in the world
                 true
@@ -133,281 +225,344 @@ impl Entity {
 }
 
 pub struct EntityFactory {
-    pub resources: Rc<Resources>,
+    pub prefabs: Rc<Prefabs>,
 }
 
 impl EntityFactory {
-    #[allow(dead_code)]
-    pub fn empty() -> Self {
-        EntityFactory {
-            resources: Rc::new(Resources::ZERO),
-        }
-    }
+    /// New entity from the prefab called `name`
+    pub fn make(
+        &self,
+        name: &str,
+        position: WorldPosition,
+        rotation: cgmath::Quaternion<f32>,
+        rng: &mut GameRng,
+    ) -> Entity {
+        let prefab = self.prefabs.get(name);
+        let forward = rotation.rotate_vector(cgmath::Vector3::unit_y()).truncate();
 
-    pub fn make_asteroid_s(&self, position: WorldPosition) -> Entity {
-        let (mesh_id, mesh) = self.resources.get_mesh_by_name("Asteroid_S").unwrap();
         Entity {
-            name: "Asteroid_S",
+            name: prefab.name,
+            kind: prefab.kind,
             position,
-            rotation: cgmath::Quaternion::from_axis_angle(cgmath::Vector3::unit_z(), Deg(0.0)),
-            renderable: Some(Renderable {
-                shader: ShaderName::Model,
-                mesh: mesh_id,
-                material: mesh.material,
-            }),
-            physics: Some(Physics::random(10., 100.)),
-            shape: Some(Shape::Circle {
-                origin: position.to_zero(),
-                radius: 1.0,
-            }),
-            light: Some(Light {
-                color: [0., 0.3, 0.7],
-                radius: 5.,
-                z: 5.,
-            }),
-            collision: Some(Collision {
-                on_collision: |gamestate, this_id, _other_ids| gamestate.kill(this_id),
+            rotation,
+            entered_world: prefab.entered_world,
+            renderable: prefab.renderable.clone(),
+            physics: prefab.physics.map(|physics| match physics.random_speed {
+                Some(random_speed) => Physics {
+                    max_linear_speed: physics.max_linear_speed,
+                    linear_damping: physics.linear_damping,
+                    angular_damping: physics.angular_damping,
+                    mass: physics.mass,
+                    restitution: physics.restitution,
+                    ..Physics::random(rng, random_speed.linear, random_speed.angular)
+                },
+                None => Physics {
+                    linear_speed: forward * physics.launch_speed,
+                    max_linear_speed: physics.max_linear_speed,
+                    angular_velocity: cgmath::Vector3::zero(),
+                    linear_damping: physics.linear_damping,
+                    angular_damping: physics.angular_damping,
+                    mass: physics.mass,
+                    restitution: physics.restitution,
+                },
             }),
+            shape: prefab
+                .shape
+                .as_ref()
+                .map(|shape| shape.place(position.to_zero())),
+            light: prefab.light,
+            lifetime: prefab.lifetime,
+            health: prefab.health,
+            hyperspace: prefab.hyperspace,
+            ai: prefab.ai,
+            weapon: prefab.weapon,
+            shield: prefab.shield,
+            control: if prefab.control {
+                Some(Control::enabled())
+            } else {
+                None
+            },
+            collision: prefab
+                .on_collision
+                .zip(prefab.layer)
+                .map(|(behaviour, (layer, mask))| Collision {
+                    layer,
+                    mask,
+                    sensor: prefab.sensor,
+                    on_collision: match behaviour {
+                        CollisionBehaviour::Shatter => shatter,
+                        CollisionBehaviour::Ship => ship_hit,
+                        CollisionBehaviour::Projectile => projectile_hit,
+                        CollisionBehaviour::EnemyProjectile => enemy_projectile_hit,
+                        CollisionBehaviour::PowerUp => picked_up,
+                    },
+                }),
             ..Default::default()
         }
     }
 
-    pub fn make_asteroid_m(&self, position: WorldPosition) -> Entity {
-        let (mesh_id, mesh) = self.resources.get_mesh_by_name("Asteroid_M").unwrap();
-        Entity {
-            name: "Asteroid_M",
-            position,
-            rotation: cgmath::Quaternion::from_axis_angle(cgmath::Vector3::unit_z(), Deg(0.0)),
-            renderable: Some(Renderable {
-                shader: ShaderName::Model,
-                mesh: mesh_id,
-                material: mesh.material,
-            }),
-            physics: Some(Physics::random(10., 100.)),
-            shape: Some(Shape::Circle {
-                origin: position.to_zero(),
-                radius: 3.0,
-            }),
-            light: Some(Light {
-                color: [0., 0.3, 0.7],
-                radius: 10.,
-                z: 10.,
-            }),
-            collision: Some(Collision {
-                on_collision: |gamestate, this_id, _other_ids| {
-                    let this_option = gamestate.get_entity(this_id);
-                    let mut to_spawn = Vec::with_capacity(2);
-                    match this_option {
-                        Some(this) => {
-                            to_spawn.push(
-                                gamestate
-                                    .entity_factory
-                                    .make_asteroid_s(this.position.translate((1.5, 0.0).into())),
-                            );
-                            to_spawn.push(
-                                gamestate
-                                    .entity_factory
-                                    .make_asteroid_s(this.position.translate((-1.5, 0.0).into())),
-                            );
-                        }
-                        None => (),
-                    }
-
-                    for e in to_spawn {
-                        gamestate.push(e);
-                    }
-
-                    gamestate.kill(this_id)
-                },
-            }),
-            ..Default::default()
+    /// Projectiles of a `shot` fired by `owner`, inheriting its speed
+    pub fn make_shot(
+        &self,
+        shot: &Shot,
+        owner: EntityId,
+        position: WorldPosition,
+        rotation: cgmath::Quaternion<f32>,
+        relative_speed: cgmath::Vector2<f32>,
+        rng: &mut GameRng,
+    ) -> Vec<Entity> {
+        let forward = rotation.rotate_vector(cgmath::Vector3::unit_y()).truncate();
+        let position = position.translate(forward * shot.muzzle_offset);
+        let first_angle = -shot.spread_angle * (shot.count - 1) as f32 / 2.;
+
+        (0..shot.count)
+            .map(|i| {
+                let angle = cgmath::Deg(first_angle + shot.spread_angle * i as f32);
+                let rotation = rotation * cgmath::Quaternion::from_angle_z(angle);
+                let mut projectile = self.make(shot.projectile, position, rotation, rng);
+                projectile.owner = Some(owner);
+                if let Some(physics) = &mut projectile.physics {
+                    physics.linear_speed += relative_speed;
+                }
+                projectile
+            })
+            .collect()
+    }
+}
+
+fn shatter(gamestate: &mut GameState, this_id: EntityId, other_ids: &[EntityId]) {
+    // Shields make asteroids bounce off, see `ship_hit`
+    if other_ids.iter().all(|id| gamestate.is_shielded(*id)) {
+        return;
+    }
+
+    if let Some(this) = gamestate.get_entity(this_id) {
+        let position = this.position();
+        let prefab = gamestate.entity_factory.prefabs.get(this.name);
+
+        // Split offsets are given for a hit from below: parts on the x axis fly apart
+        // perpendicular to the hit, on top of the velocity of the whole
+        let velocity = this
+            .physics
+            .map(|physics| physics.linear_speed)
+            .unwrap_or_else(cgmath::Vector2::zero);
+        let along = hit_direction(gamestate, this_id, other_ids);
+        let across = cgmath::vec2(along.y, -along.x);
+
+        for split in &prefab.splits_into {
+            let offset = across * split.offset.0 + along * split.offset.1;
+            let mut part = gamestate.entity_factory.make(
+                &split.prefab,
+                position.translate(offset),
+                cgmath::Quaternion::one(),
+                &mut gamestate.rng,
+            );
+            if let Some(physics) = &mut part.physics {
+                let push = if offset.magnitude2() > 0. {
+                    offset.normalize() * prefab.split_impulse / physics.mass
+                } else {
+                    cgmath::Vector2::zero()
+                };
+                physics.linear_speed = velocity + push;
+            }
+            gamestate.commands.spawn(part);
+        }
+
+        if let Some(drops) = &prefab.drops {
+            let rng = &mut gamestate.rng;
+            if rng.gen_bool(drops.chance) {
+                let power_up = &drops.prefabs[rng.gen_range(0..drops.prefabs.len())];
+                let power_up = gamestate.entity_factory.make(
+                    power_up,
+                    position,
+                    cgmath::Quaternion::one(),
+                    &mut gamestate.rng,
+                );
+                gamestate.commands.spawn(power_up);
+            }
         }
     }
 
-    pub fn make_asteroid_l(&self, position: WorldPosition) -> Entity {
-        let (mesh_id, mesh) = self.resources.get_mesh_by_name("Asteroid_L").unwrap();
-        Entity {
-            name: "Asteroid_L",
-            position,
-            rotation: cgmath::Quaternion::from_axis_angle(cgmath::Vector3::unit_z(), Deg(0.0)),
-            renderable: Some(Renderable {
-                shader: ShaderName::Model,
-                mesh: mesh_id,
-                material: mesh.material,
-            }),
-            physics: Some(Physics::random(5., 100.)),
-            shape: Some(Shape::Circle {
-                origin: position.to_zero(),
-                radius: 5.0,
-            }),
-            light: Some(Light {
-                color: [0., 0.3, 0.7],
-                radius: 15.,
-                z: 15.,
-            }),
-            collision: Some(Collision {
-                on_collision: |gamestate, this_id, _other_ids| {
-                    let mut to_spawn = Vec::with_capacity(2);
-                    if let Some(this) = gamestate.get_entity(this_id) {
-                        to_spawn.push(
-                            gamestate
-                                .entity_factory
-                                .make_asteroid_m(this.position.translate((3.5, 0.0).into())),
-                        );
-                        to_spawn.push(
-                            gamestate
-                                .entity_factory
-                                .make_asteroid_m(this.position.translate((-3.5, 0.0).into())),
-                        );
-                        to_spawn.push(
-                            gamestate
-                                .entity_factory
-                                .make_cloud(this.position, cgmath::Quaternion::zero()),
-                        )
-                    }
-
-                    for e in to_spawn {
-                        gamestate.push(e);
-                    }
-
-                    gamestate.kill(this_id)
-                },
-            }),
-            ..Default::default()
+    gamestate.commands.despawn(this_id)
+}
+
+/// Unit vector of the direction the first harmful contact hit `this_id` in
+fn hit_direction(
+    gamestate: &GameState,
+    this_id: EntityId,
+    other_ids: &[EntityId],
+) -> cgmath::Vector2<f32> {
+    let this = gamestate.get_entity(this_id);
+    let hitter = other_ids
+        .iter()
+        .filter(|id| !gamestate.is_shielded(**id))
+        .find_map(|id| gamestate.get_entity(*id));
+
+    if let (Some(this), Some(hitter)) = (this, hitter) {
+        let speed = |entity: &Entity| {
+            entity
+                .physics
+                .map(|physics| physics.linear_speed)
+                .unwrap_or_else(cgmath::Vector2::zero)
+        };
+        let relative_speed = speed(hitter) - speed(this);
+        if relative_speed.magnitude2() > 0. {
+            return relative_speed.normalize();
+        }
+
+        let offset = hitter.position().delta(&this.position());
+        if offset.magnitude2() > 0. {
+            return offset.normalize();
         }
     }
 
-    pub fn make_spaceship(&self, position: WorldPosition, rotation_angle: f32) -> Entity {
-        let (mesh_id, mesh) = self.resources.get_mesh_by_name("Spaceship").unwrap();
-        Entity {
-            name: "Spaceship",
-            position,
-            rotation: cgmath::Quaternion::from_angle_z(Deg(rotation_angle)),
-            renderable: Some(Renderable {
-                shader: ShaderName::Model,
-                mesh: mesh_id,
-                material: mesh.material,
-            }),
-            physics: Some(Physics {
-                max_linear_speed: 60.,
-                ..Default::default()
-            }),
-            shape: Some(Shape::Circle {
-                origin: position.to_zero(),
-                radius: 5.0,
-            }),
-            light: Some(Light {
-                color: [1., 0.7, 0.3],
-                radius: 30.,
-                z: 15.,
-            }),
-            collision: Some(Collision {
-                on_collision: |gamestate, this_id, other_ids| {
-                    let asteroids_number = other_ids
-                        .iter()
-                        .flat_map(|id| gamestate.get_entity(*id))
-                        .filter(|entity| entity.name.starts_with("Asteroid"))
-                        .count();
-
-                    let this = gamestate.get_entity_mut(this_id).unwrap();
-
-                    match &mut this.health {
-                        Some(health) => {
-                            health.deal_damage(asteroids_number);
-                            if health.level == 0 {
-                                gamestate.kill(this_id);
-                            }
-                        }
-                        None => (),
-                    }
-                },
-            }),
-            control: Some(Control::enabled()),
-            health: Some(Health {
-                level: 3,
-                invincible: false,
-            }),
-            ..Default::default()
+    cgmath::Vector2::unit_y()
+}
+
+fn ship_hit(gamestate: &mut GameState, this_id: EntityId, other_ids: &[EntityId]) {
+    if gamestate.is_shielded(this_id) {
+        bounce_off(gamestate, this_id, other_ids);
+        return;
+    }
+
+    // Only saucer projectiles get here, the ship's layer doesn't touch its own, see `res/collision_layers.ron`
+    let hits = other_ids
+        .iter()
+        .filter(|id| {
+            gamestate.is(**id, Kind::is_asteroid)
+                || gamestate.is(**id, Kind::is_saucer)
+                || gamestate.is(**id, Kind::is_projectile)
+        })
+        .count();
+
+    let this = gamestate.get_entity_mut(this_id).unwrap();
+
+    if let Some(health) = &mut this.health {
+        let level_before = health.level;
+        health.deal_damage(hits);
+        let level = health.level;
+
+        // Destroyed ships are taken care of by `GameState::lives_system`
+        if level < level_before {
+            gamestate.events.publish(GameEvent::ShipDamaged {
+                id: this_id,
+                health: level,
+            });
         }
     }
+}
 
-    pub fn make_laser(
-        &self,
-        position: WorldPosition,
-        rotation: cgmath::Quaternion<f32>,
-        relative_speed: cgmath::Vector2<f32>,
-    ) -> Entity {
-        let init_speed = 80.;
-        let (mesh_id, mesh) = self.resources.get_mesh_by_name("Laser").unwrap();
-        Entity {
-            name: "Laser",
-            position,
-            rotation,
-            renderable: Some(Renderable {
-                shader: ShaderName::Model,
-                mesh: mesh_id,
-                material: mesh.material,
-            }),
-            physics: Some(Physics {
-                linear_speed: (rotation.rotate_vector(cgmath::Vector3::unit_y())).truncate()
-                    * init_speed
-                    + relative_speed,
-                max_linear_speed: 1000.,
-                angular_speed: cgmath::Quaternion::zero(),
-            }),
-            lifetime: Some(Lifetime {
-                dies_after: Duration::from_secs(1),
-            }),
-            shape: Some(Shape::Circle {
-                origin: position.to_zero(),
-                radius: 1.,
-            }),
-            light: Some(Light {
-                color: [1., 0.7, 0.3],
-                radius: 10.,
-                z: 0.,
-            }),
-            collision: Some(Collision {
-                on_collision: |gamestate, this_id, other_ids| {
-                    let mut should_kill_self = false;
-
-                    for id in other_ids {
-                        match gamestate.get_entity(*id) {
-                            Some(other) => {
-                                if other.name.starts_with("Asteroid") {
-                                    gamestate.score += 1;
-                                    should_kill_self = true;
-                                }
-                            }
-                            None => (),
-                        }
-                    }
-
-                    if should_kill_self {
-                        gamestate.kill(this_id);
-                    }
-                },
-            }),
-            ..Default::default()
+/// Power-ups take effect as soon as the ship picks them up, see `picked_up`
+fn apply_power_up(gamestate: &mut GameState, this_id: EntityId, effect: PowerUpEffect) {
+    let this = match gamestate.get_entity(this_id) {
+        Some(this) => this,
+        None => return,
+    };
+    let modifiers = this.modifiers.unwrap_or_default();
+    let component = match effect {
+        PowerUpEffect::Spread => Some(Component::Modifiers(Modifiers {
+            spread_for: Modifiers::DURATION,
+            ..modifiers
+        })),
+        PowerUpEffect::RapidFire => Some(Component::Modifiers(Modifiers {
+            rapid_fire_for: Modifiers::DURATION,
+            ..modifiers
+        })),
+        PowerUpEffect::ExtraHealth => {
+            let prefab = gamestate.entity_factory.prefabs.get(this.name);
+            let max_level = prefab.health.map(|health| health.level);
+            match (this.health, max_level) {
+                (Some(health), Some(max_level)) => Some(Component::Health(Health {
+                    level: (health.level + 1).min(max_level),
+                    ..health
+                })),
+                _ => None,
+            }
         }
+        PowerUpEffect::Invincibility => this.health.map(|health| {
+            Component::Health(Health {
+                invincible_for: Modifiers::DURATION,
+                ..health
+            })
+        }),
+    };
+
+    if let Some(component) = component {
+        gamestate.commands.insert(this_id, component);
     }
+    gamestate.events.publish(GameEvent::PowerUpCollected {
+        id: this_id,
+        effect,
+    });
+}
 
-    pub fn make_cloud(&self, position: WorldPosition, rotation: cgmath::Quaternion<f32>) -> Entity {
-        let (mesh_id, mesh) = self.resources.get_mesh_by_name("Cloud_L").unwrap();
-        Entity {
-            name: "Cloud_L",
-            position,
-            rotation,
-            entered_world: true,
-            renderable: Some(Renderable {
-                shader: ShaderName::Texture,
-                mesh: mesh_id,
-                material: mesh.material,
-            }),
-            lifetime: Some(Lifetime {
-                dies_after: Duration::from_secs(1),
-            }),
-            ..Default::default()
+/// Reflect the speed of approaching asteroids off the shield
+fn bounce_off(gamestate: &mut GameState, this_id: EntityId, other_ids: &[EntityId]) {
+    let this_position = match gamestate.get_entity(this_id) {
+        Some(this) => this.position(),
+        None => return,
+    };
+
+    for id in other_ids {
+        if !gamestate.is(*id, Kind::is_asteroid) {
+            continue;
         }
+
+        if let Some(asteroid) = gamestate.get_entity(*id) {
+            let offset = this_position.delta(&asteroid.position());
+            if let (Some(mut physics), true) = (asteroid.physics, offset.magnitude2() > 0.) {
+                let normal = offset.normalize();
+                let approach_speed = physics.linear_speed.dot(normal);
+                if approach_speed < 0. {
+                    physics.linear_speed -= normal * 2. * approach_speed;
+                    gamestate.commands.insert(*id, Component::Physics(physics));
+                }
+            }
+        }
+    }
+}
+
+fn projectile_hit(gamestate: &mut GameState, this_id: EntityId, other_ids: &[EntityId]) {
+    let mut should_kill_self = false;
+
+    for id in other_ids {
+        if let Some(kind) = gamestate.get_entity(*id).and_then(|entity| entity.kind) {
+            if kind.is_asteroid() || kind.is_saucer() {
+                gamestate.commands.add_score(kind.points());
+                should_kill_self = true;
+            }
+        }
+    }
+
+    if should_kill_self {
+        gamestate.commands.despawn(this_id);
+    }
+}
+
+/// Power-ups are sensors, so the ship picks them up once, when it enters them
+fn picked_up(gamestate: &mut GameState, this_id: EntityId, other_ids: &[EntityId]) {
+    let effect = match gamestate.get_entity(this_id).and_then(|this| this.kind) {
+        Some(Kind::PowerUp { effect }) => effect,
+        _ => return,
+    };
+
+    if let Some(ship_id) = other_ids
+        .iter()
+        .copied()
+        .find(|id| gamestate.is(*id, Kind::is_ship))
+    {
+        apply_power_up(gamestate, ship_id, effect);
+        gamestate.commands.despawn(this_id);
+    }
+}
+
+/// Like `projectile_hit`, but hurts the ship and doesn't score
+fn enemy_projectile_hit(gamestate: &mut GameState, this_id: EntityId, other_ids: &[EntityId]) {
+    let hit_something = other_ids
+        .iter()
+        .any(|id| gamestate.is(*id, Kind::is_ship) || gamestate.is(*id, Kind::is_asteroid));
+
+    if hit_something {
+        gamestate.commands.despawn(this_id);
     }
 }
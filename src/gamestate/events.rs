@@ -0,0 +1,135 @@
+use super::components::PowerUpEffect;
+use super::world::WorldPosition;
+use super::EntityId;
+use std::fmt::Display;
+
+#[derive(Clone, Debug)]
+pub enum GameEvent {
+    /// `id` touched all of `other_ids` during a simulation step
+    Collided {
+        id: EntityId,
+        other_ids: Vec<EntityId>,
+    },
+    /// `other_id` started to overlap sensor `id`
+    TriggerEntered {
+        id: EntityId,
+        other_id: EntityId,
+    },
+    /// `other_id` still overlaps sensor `id`, published every simulation step after `TriggerEntered`
+    TriggerStayed {
+        id: EntityId,
+        other_id: EntityId,
+    },
+    /// `other_id` no longer overlaps sensor `id`, or one of them is gone
+    TriggerExited {
+        id: EntityId,
+        other_id: EntityId,
+    },
+    EntityDestroyed {
+        id: EntityId,
+        name: &'static str,
+        position: WorldPosition,
+    },
+    ScoreChanged {
+        score: usize,
+    },
+    LevelStarted {
+        level: usize,
+    },
+    ShipDamaged {
+        id: EntityId,
+        health: usize,
+    },
+    /// The ship has been destroyed, `lives` are left
+    LifeLost {
+        lives: usize,
+    },
+    ShipRespawned,
+    HyperspaceEntered {
+        id: EntityId,
+    },
+    /// `destroyed` if the entity didn't survive the jump
+    HyperspaceExited {
+        id: EntityId,
+        position: WorldPosition,
+        destroyed: bool,
+    },
+    PowerUpCollected {
+        id: EntityId,
+        effect: PowerUpEffect,
+    },
+    GameOver {
+        score: usize,
+    },
+}
+
+impl Display for GameEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GameEvent::Collided { id, other_ids } => {
+                write!(f, "{:?} collided with {:?}", id, other_ids)
+            }
+            GameEvent::TriggerEntered { id, other_id } => {
+                write!(f, "{:?} entered trigger {:?}", other_id, id)
+            }
+            GameEvent::TriggerStayed { id, other_id } => {
+                write!(f, "{:?} stays in trigger {:?}", other_id, id)
+            }
+            GameEvent::TriggerExited { id, other_id } => {
+                write!(f, "{:?} exited trigger {:?}", other_id, id)
+            }
+            GameEvent::EntityDestroyed { id, name, position } => {
+                write!(f, "{} {:?} destroyed at {}", name, id, position)
+            }
+            GameEvent::ScoreChanged { score } => write!(f, "Score is {}", score),
+            GameEvent::LevelStarted { level } => write!(f, "Level {} started", level),
+            GameEvent::ShipDamaged { id, health } => {
+                write!(f, "Ship {:?} damaged, health is {}", id, health)
+            }
+            GameEvent::LifeLost { lives } => write!(f, "Life lost, {} left", lives),
+            GameEvent::ShipRespawned => write!(f, "Ship respawned"),
+            GameEvent::HyperspaceEntered { id } => write!(f, "{:?} entered hyperspace", id),
+            GameEvent::HyperspaceExited {
+                id,
+                position,
+                destroyed,
+            } => write!(
+                f,
+                "{:?} exited hyperspace at {}{}",
+                id,
+                position,
+                if *destroyed { " and was destroyed" } else { "" }
+            ),
+            GameEvent::PowerUpCollected { id, effect } => {
+                write!(f, "{:?} collected {:?}", id, effect)
+            }
+            GameEvent::GameOver { score } => write!(f, "Game over, score is {}", score),
+        }
+    }
+}
+
+/// Events published by systems during a frame.
+/// Readers (UI, audio, statistics, tests) either look at them with `iter` or take them with `drain`.
+/// Whatever is left is dropped when the next frame starts, so the channel never grows unbounded.
+#[derive(Default)]
+pub struct Events {
+    events: Vec<GameEvent>,
+}
+
+impl Events {
+    pub fn publish(&mut self, event: GameEvent) {
+        self.events.push(event);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &GameEvent> {
+        self.events.iter()
+    }
+
+    pub fn drain(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
@@ -1,3 +1,6 @@
+use cgmath::{InnerSpace, Rotation, Rotation2};
+use std::sync::Arc;
+
 use super::world::WorldPosition;
 
 pub struct Rect {
@@ -55,30 +58,349 @@ fn test_rect_contains_circle() {
     assert_eq!(rect.contains_circle((-40., 0.), 11.), false);
 }
 
+/// Where two overlapping shapes touch
 #[derive(Debug, Clone, Copy)]
+pub struct Contact {
+    /// Unit vector from the first shape towards the second one, along the shortest path in the world
+    pub normal: cgmath::Vector2<f32>,
+    /// How deep the shapes overlap along the normal
+    pub depth: f32,
+    /// Contact point relative to the centre of each shape
+    pub offsets: (cgmath::Vector2<f32>, cgmath::Vector2<f32>),
+}
+
+/// Collision shape. Polygons and capsules turn with their `rotation`, in radians around the Z axis.
+#[derive(Debug, Clone)]
 pub enum Shape {
-    Circle { origin: WorldPosition, radius: f32 },
+    Circle {
+        origin: WorldPosition,
+        radius: f32,
+    },
+    /// Convex, with points around the origin in counter-clockwise order, see `convex_hull`
+    Polygon {
+        origin: WorldPosition,
+        rotation: f32,
+        points: Arc<[cgmath::Vector2<f32>]>,
+    },
+    /// Segment along the local Y axis, rounded by `radius`
+    Capsule {
+        origin: WorldPosition,
+        rotation: f32,
+        half_length: f32,
+        radius: f32,
+    },
 }
 
 impl Shape {
-    pub(crate) fn overlaps(&self, another_shape: &Shape) -> bool {
-        match (self, another_shape) {
-            (
-                Shape::Circle { origin, radius },
-                Shape::Circle {
-                    origin: other_origin,
-                    radius: other_radius,
-                },
-            ) => WorldPosition::distance(origin, other_origin) < (radius + other_radius),
+    /// Separating axis test. Every shape is a convex core, a point, a segment or a polygon,
+    /// rounded by a radius, so that all pairings are handled the same way.
+    pub(crate) fn contact(&self, another_shape: &Shape) -> Option<Contact> {
+        let (core, radius) = self.core(cgmath::vec2(0., 0.));
+        let (other_core, other_radius) =
+            another_shape.core(self.origin().delta(&another_shape.origin()));
+        let rounded = radius + other_radius;
+
+        let mut axes = edge_normals(&core);
+        axes.extend(edge_normals(&other_core));
+        // Rounded cores can also be separated along the line between their closest points
+        if rounded > 0. {
+            for point in &core {
+                for other_point in &other_core {
+                    let direction = other_point - point;
+                    if direction.magnitude2() > 0. {
+                        axes.push(direction.normalize());
+                    }
+                }
+            }
+        }
+
+        let mut best: Option<(f32, cgmath::Vector2<f32>)> = None;
+        for axis in axes {
+            let (min, max) = project(&core, axis);
+            let (other_min, other_max) = project(&other_core, axis);
+            let overlap = (max + radius).min(other_max + other_radius)
+                - (min - radius).max(other_min - other_radius);
+            if overlap <= 0. {
+                return None;
+            }
+            if !matches!(best, Some((depth, _)) if depth <= overlap) {
+                best = Some((overlap, axis));
+            }
+        }
+
+        // Without any axis, both cores are the same point
+        let (depth, axis) = best.unwrap_or((rounded, cgmath::Vector2::unit_x()));
+        let towards_other = centroid(&other_core) - centroid(&core);
+        let normal = if axis.dot(towards_other) < 0. {
+            -axis
+        } else {
+            axis
+        };
+
+        let other_offset = self.origin().delta(&another_shape.origin());
+        Some(Contact {
+            normal,
+            depth,
+            offsets: (
+                support(&core, normal) + normal * radius,
+                support(&other_core, -normal) - normal * other_radius - other_offset,
+            ),
+        })
+    }
+
+    /// Points of the convex core relative to the origin of another shape that is `offset` away,
+    /// and the radius that rounds them
+    fn core(&self, offset: cgmath::Vector2<f32>) -> (Vec<cgmath::Vector2<f32>>, f32) {
+        match self {
+            Shape::Circle { radius, .. } => (vec![offset], *radius),
+            Shape::Polygon {
+                rotation, points, ..
+            } => {
+                let rotation = cgmath::Basis2::from_angle(cgmath::Rad(*rotation));
+                let points = points
+                    .iter()
+                    .map(|point| offset + rotation.rotate_vector(*point))
+                    .collect();
+                (points, 0.)
+            }
+            Shape::Capsule {
+                rotation,
+                half_length,
+                radius,
+                ..
+            } => {
+                let rotation = cgmath::Basis2::from_angle(cgmath::Rad(*rotation));
+                let axis = rotation.rotate_vector(cgmath::Vector2::unit_y()) * *half_length;
+                (vec![offset - axis, offset + axis], *radius)
+            }
         }
     }
 
-    pub(crate) fn translate(&self, position: cgmath::Vector2<f32>) -> Shape {
-        match *self {
-            Shape::Circle { origin, radius } => Shape::Circle {
-                origin: origin.translate(position),
+    pub(crate) fn origin(&self) -> WorldPosition {
+        match self {
+            Shape::Circle { origin, .. }
+            | Shape::Polygon { origin, .. }
+            | Shape::Capsule { origin, .. } => *origin,
+        }
+    }
+
+    /// Radius of the smallest circle around the shape's origin that contains it, whatever the rotation
+    pub(crate) fn bounding_radius(&self) -> f32 {
+        match self {
+            Shape::Circle { radius, .. } => *radius,
+            Shape::Polygon { points, .. } => points
+                .iter()
+                .map(|point| point.magnitude())
+                .fold(0., f32::max),
+            Shape::Capsule {
+                half_length,
                 radius,
+                ..
+            } => half_length + radius,
+        }
+    }
+
+    /// Smallest width of the shape, in any direction
+    pub(crate) fn thickness(&self) -> f32 {
+        let (core, radius) = self.core(cgmath::vec2(0., 0.));
+        let core_width = if core.len() > 2 {
+            edge_normals(&core)
+                .into_iter()
+                .map(|axis| {
+                    let (min, max) = project(&core, axis);
+                    max - min
+                })
+                .fold(f32::INFINITY, f32::min)
+        } else {
+            0.
+        };
+
+        core_width + 2. * radius
+    }
+
+    pub(crate) fn translate(&self, position: cgmath::Vector2<f32>) -> Shape {
+        let mut shape = self.clone();
+        match &mut shape {
+            Shape::Circle { origin, .. }
+            | Shape::Polygon { origin, .. }
+            | Shape::Capsule { origin, .. } => *origin = origin.translate(position),
+        }
+        shape
+    }
+
+    /// Turn by `angle` radians around the origin
+    pub(crate) fn rotate(&self, angle: f32) -> Shape {
+        let mut shape = self.clone();
+        match &mut shape {
+            Shape::Circle { .. } => (),
+            Shape::Polygon { rotation, .. } | Shape::Capsule { rotation, .. } => *rotation += angle,
+        }
+        shape
+    }
+}
+
+/// Normals of the edges of a convex polygon. A segment has a single edge, a point none.
+fn edge_normals(points: &[cgmath::Vector2<f32>]) -> Vec<cgmath::Vector2<f32>> {
+    let edges = match points.len() {
+        0 | 1 => 0,
+        2 => 1,
+        n => n,
+    };
+
+    (0..edges)
+        .filter_map(|i| {
+            let edge = points[(i + 1) % points.len()] - points[i];
+            if edge.magnitude2() > 0. {
+                Some(cgmath::vec2(-edge.y, edge.x).normalize())
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn project(points: &[cgmath::Vector2<f32>], axis: cgmath::Vector2<f32>) -> (f32, f32) {
+    points
+        .iter()
+        .map(|point| point.dot(axis))
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), x| {
+            (min.min(x), max.max(x))
+        })
+}
+
+/// The point furthest along `direction`
+fn support(
+    points: &[cgmath::Vector2<f32>],
+    direction: cgmath::Vector2<f32>,
+) -> cgmath::Vector2<f32> {
+    points
+        .iter()
+        .copied()
+        .fold(
+            None,
+            |best: Option<cgmath::Vector2<f32>>, point| match best {
+                Some(best) if best.dot(direction) >= point.dot(direction) => Some(best),
+                _ => Some(point),
             },
+        )
+        .unwrap_or_else(|| cgmath::vec2(0., 0.))
+}
+
+fn centroid(points: &[cgmath::Vector2<f32>]) -> cgmath::Vector2<f32> {
+    points
+        .iter()
+        .fold(cgmath::vec2(0., 0.), |sum, point| sum + point)
+        / points.len().max(1) as f32
+}
+
+/// Smallest convex polygon containing all `points`, in counter-clockwise order (Andrew's monotone chain)
+pub fn convex_hull(points: &[cgmath::Vector2<f32>]) -> Vec<cgmath::Vector2<f32>> {
+    let mut points = points.to_vec();
+    points.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+
+    let cross = |o: cgmath::Vector2<f32>, a: cgmath::Vector2<f32>, b: cgmath::Vector2<f32>| {
+        (a.x - o.x) * (b.y - o.y) - (a.y - o.y) * (b.x - o.x)
+    };
+
+    let mut hull: Vec<cgmath::Vector2<f32>> = vec![];
+    for pass in 0..2 {
+        let start = hull.len();
+        let ordered: Box<dyn Iterator<Item = &cgmath::Vector2<f32>>> = if pass == 0 {
+            Box::new(points.iter())
+        } else {
+            Box::new(points.iter().rev())
+        };
+        for point in ordered {
+            while hull.len() >= start + 2
+                && cross(hull[hull.len() - 2], hull[hull.len() - 1], *point) <= 0.
+            {
+                hull.pop();
+            }
+            hull.push(*point);
         }
+        // The last point of each half is the first one of the other
+        hull.pop();
     }
+
+    hull
+}
+
+#[test]
+fn test_convex_hull() {
+    let points = [
+        (0., 0.),
+        (2., 0.),
+        (1., 1.),
+        (2., 2.),
+        (0., 2.),
+        (1., 0.),
+        (0., 2.),
+    ]
+    .map(cgmath::Vector2::from);
+
+    let hull = convex_hull(&points);
+    assert_eq!(
+        hull,
+        [(0., 0.), (2., 0.), (2., 2.), (0., 2.)].map(cgmath::Vector2::from)
+    );
+}
+
+#[test]
+fn test_shape_contact() {
+    use super::world::World;
+
+    let world = World::init(1.0);
+    let at = |x: f32, y: f32| world.new_position((x, y).into());
+    let square: Arc<[cgmath::Vector2<f32>]> = Arc::from(convex_hull(
+        &[(-1., -1.), (1., -1.), (1., 1.), (-1., 1.)].map(Into::into),
+    ));
+    let polygon = |x: f32, rotation: f32| Shape::Polygon {
+        origin: at(x, 0.),
+        rotation,
+        points: square.clone(),
+    };
+
+    // Corners of a turned square reach further
+    assert!(polygon(0., 0.).contact(&polygon(2.2, 0.)).is_none());
+    let contact = polygon(0., 0.)
+        .contact(&polygon(2.2, std::f32::consts::FRAC_PI_4))
+        .unwrap();
+    assert!((contact.normal - cgmath::Vector2::unit_x()).magnitude() < 1e-4);
+    assert!((contact.depth - (2f32.sqrt() - 1.2)).abs() < 1e-4);
+
+    // A circle next to a corner, but not touching it
+    let circle = |x: f32, y: f32| Shape::Circle {
+        origin: at(x, y),
+        radius: 1.,
+    };
+    assert!(polygon(0., 0.).contact(&circle(1.75, 1.75)).is_none());
+    assert!(polygon(0., 0.).contact(&circle(1.5, 1.5)).is_some());
+
+    // A lying capsule reaches sideways, a standing one doesn't
+    let capsule = |rotation: f32| Shape::Capsule {
+        origin: at(0., 0.),
+        rotation,
+        half_length: 3.,
+        radius: 0.5,
+    };
+    assert!(capsule(0.).contact(&circle(2.5, 0.)).is_none());
+    assert!(capsule(std::f32::consts::FRAC_PI_2)
+        .contact(&circle(2.5, 0.))
+        .is_some());
+    assert!(capsule(0.)
+        .contact(&capsule(0.).translate((0.9, 0.).into()))
+        .is_some());
+    assert!(capsule(0.)
+        .contact(&capsule(0.).translate((1.1, 0.).into()))
+        .is_none());
+
+    // Across the world's edge
+    let (w, _h) = world.size;
+    assert!(polygon(w / 2. - 0.5, 0.)
+        .contact(&polygon(-w / 2. + 0.5, 0.))
+        .is_some());
 }
@@ -0,0 +1,794 @@
+use anyhow::*;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::components::{
+    Ai, CollisionLayer, Health, Hyperspace, Kind, Lifetime, Light, Renderable, Shield, Weapon,
+};
+use super::geometry::Shape;
+use super::world::WorldPosition;
+use crate::assets::{self, AssetCatalogue};
+use crate::shaders::ShaderName;
+
+/// Prefabs the game spawns by name, so they must be present in every prefab file
+const REQUIRED_PREFABS: [&str; 6] = [
+    "Spaceship",
+    "Laser",
+    "Asteroid_L",
+    "Saucer_L",
+    "Saucer_S",
+    "Enemy_Laser",
+];
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PrefabDef {
+    #[serde(default)]
+    kind: Option<Kind>,
+    #[serde(default)]
+    renderable: Option<RenderableDef>,
+    #[serde(default)]
+    physics: Option<PhysicsDef>,
+    #[serde(default)]
+    shape: Option<ShapeDef>,
+    #[serde(default)]
+    light: Option<Light>,
+    /// Seconds
+    #[serde(default)]
+    lifetime: Option<f32>,
+    #[serde(default)]
+    health: Option<usize>,
+    #[serde(default)]
+    control: bool,
+    #[serde(default)]
+    hyperspace: Option<HyperspaceDef>,
+    #[serde(default)]
+    shield: Option<ShieldDef>,
+    #[serde(default)]
+    ai: Option<AiDef>,
+    #[serde(default)]
+    weapon: Option<WeaponDef>,
+    #[serde(default)]
+    on_collision: Option<CollisionBehaviour>,
+    #[serde(default)]
+    layer: Option<CollisionLayer>,
+    /// Only reports overlaps, see `Collision::sensor`
+    #[serde(default)]
+    sensor: bool,
+    #[serde(default)]
+    entered_world: bool,
+    #[serde(default)]
+    splits_into: Vec<Split>,
+    /// Pushes the parts apart when the prefab shatters, see `Split`
+    #[serde(default)]
+    split_impulse: f32,
+    #[serde(default)]
+    drops: Option<Drops>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RenderableDef {
+    mesh: String,
+    shader: ShaderName,
+    #[serde(default = "unit_scale")]
+    scale: f32,
+}
+
+fn unit_scale() -> f32 {
+    1.
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PhysicsDef {
+    pub max_linear_speed: f32,
+    /// Speed along the facing direction at spawn
+    #[serde(default)]
+    pub launch_speed: f32,
+    #[serde(default)]
+    pub random_speed: Option<RandomSpeed>,
+    /// Share of the speed lost per second
+    #[serde(default)]
+    pub linear_damping: f32,
+    #[serde(default)]
+    pub angular_damping: f32,
+    #[serde(default = "unit_mass")]
+    pub mass: f32,
+    /// Makes a rigid body, see `Physics::restitution`
+    #[serde(default)]
+    pub restitution: Option<f32>,
+}
+
+fn unit_mass() -> f32 {
+    1.
+}
+
+/// Upper bounds for a random initial speed, see `Physics::random`
+#[derive(Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RandomSpeed {
+    pub linear: f32,
+    /// Degrees per second
+    pub angular: f32,
+}
+
+/// Times are in seconds
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HyperspaceDef {
+    delay: f32,
+    cooldown: f32,
+    self_destruct_chance: f64,
+}
+
+/// Energy, and energy per second for `drain` and `recharge`
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ShieldDef {
+    energy: f32,
+    drain: f32,
+    recharge: f32,
+}
+
+/// Speed in units per second, interval in seconds and aiming error in degrees
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AiDef {
+    speed: f32,
+    course_change_interval: f32,
+    aim_error: f32,
+}
+
+/// Fire interval in seconds, spread angle in degrees, heat cooling per second
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct WeaponDef {
+    projectile: String,
+    fire_interval: f32,
+    #[serde(default = "one")]
+    spread_count: usize,
+    #[serde(default)]
+    spread_angle: f32,
+    #[serde(default)]
+    muzzle_offset: f32,
+    #[serde(default)]
+    ammo: Option<usize>,
+    #[serde(default)]
+    heat_per_shot: f32,
+    #[serde(default)]
+    cooling: f32,
+}
+
+fn one() -> usize {
+    1
+}
+
+#[derive(Clone, Copy, Deserialize)]
+pub enum ShapeDef {
+    Circle {
+        radius: f32,
+    },
+    /// Lies along the prefab's forward direction, `half_length` each way from the centre
+    Capsule {
+        half_length: f32,
+        radius: f32,
+    },
+    /// Convex outline of the renderable's mesh
+    Hull,
+}
+
+/// Collision shape of a prefab, with mesh outlines resolved
+#[derive(Clone)]
+pub enum PrefabShape {
+    Circle { radius: f32 },
+    Capsule { half_length: f32, radius: f32 },
+    Polygon { points: Arc<[cgmath::Vector2<f32>]> },
+}
+
+impl PrefabShape {
+    pub fn place(&self, origin: WorldPosition) -> Shape {
+        match self {
+            PrefabShape::Circle { radius } => Shape::Circle {
+                origin,
+                radius: *radius,
+            },
+            PrefabShape::Capsule {
+                half_length,
+                radius,
+            } => Shape::Capsule {
+                origin,
+                rotation: 0.,
+                half_length: *half_length,
+                radius: *radius,
+            },
+            PrefabShape::Polygon { points } => Shape::Polygon {
+                origin,
+                rotation: 0.,
+                points: points.clone(),
+            },
+        }
+    }
+}
+
+/// Collision callbacks can't live in data files, so prefabs pick one by name
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum CollisionBehaviour {
+    /// Destroyed on any contact, leaving `splits_into` behind
+    Shatter,
+    /// Takes damage from asteroids
+    Ship,
+    /// Scores and disappears when it hits an asteroid or a saucer
+    Projectile,
+    /// Disappears when it hits the ship or an asteroid
+    EnemyProjectile,
+    /// Disappears when the ship picks it up
+    PowerUp,
+}
+
+/// Another prefab spawned when this one shatters, relative to its position.
+/// The offset is given for a hit coming from below, and turns with the actual hit direction.
+/// Parts keep the velocity of the whole, plus `split_impulse` along the offset divided by their mass.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Split {
+    pub prefab: String,
+    #[serde(default)]
+    pub offset: (f32, f32),
+}
+
+/// Random pickup left behind when a prefab shatters
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Drops {
+    /// From 0 to 1
+    pub chance: f64,
+    /// One of them is picked at random
+    pub prefabs: Vec<String>,
+}
+
+/// Validated prefab, with meshes resolved against the asset catalogue
+pub struct Prefab {
+    pub name: &'static str,
+    pub kind: Option<Kind>,
+    pub renderable: Option<Renderable>,
+    pub physics: Option<PhysicsDef>,
+    pub shape: Option<PrefabShape>,
+    pub light: Option<Light>,
+    pub lifetime: Option<Lifetime>,
+    pub health: Option<Health>,
+    pub control: bool,
+    pub hyperspace: Option<Hyperspace>,
+    pub shield: Option<Shield>,
+    pub ai: Option<Ai>,
+    pub weapon: Option<Weapon>,
+    pub on_collision: Option<CollisionBehaviour>,
+    /// Set along with `on_collision`, with the bits of the layers it touches
+    pub layer: Option<(CollisionLayer, u32)>,
+    pub sensor: bool,
+    pub entered_world: bool,
+    pub splits_into: Vec<Split>,
+    pub split_impulse: f32,
+    pub drops: Option<Drops>,
+}
+
+/// Which collision layers touch each other, loaded from `res/collision_layers.ron`
+#[derive(Deserialize)]
+#[serde(transparent)]
+pub struct Interactions(Vec<(CollisionLayer, CollisionLayer)>);
+
+impl Interactions {
+    pub fn load() -> Result<Self> {
+        let path = assets::res_dir().join("collision_layers.ron");
+        let source =
+            std::fs::read_to_string(&path).with_context(|| format!("Can't load {:?}", path))?;
+        ron::de::from_str(&source)
+            .map_err(|error| anyhow!("{}", error))
+            .with_context(|| format!("Invalid {:?}", path))
+    }
+
+    /// Bits of the layers that `layer` touches
+    pub fn mask(&self, layer: CollisionLayer) -> u32 {
+        self.0
+            .iter()
+            .filter_map(|(a, b)| match (*a == layer, *b == layer) {
+                (true, _) => Some(b.bit()),
+                (_, true) => Some(a.bit()),
+                _ => None,
+            })
+            .fold(0, |mask, bit| mask | bit)
+    }
+}
+
+/// Entity templates loaded from `res/prefabs.ron`, so they can be tuned without touching the code
+pub struct Prefabs {
+    prefabs: BTreeMap<String, Prefab>,
+}
+
+impl Prefabs {
+    pub fn load(catalogue: &AssetCatalogue) -> Result<Self> {
+        let interactions = Interactions::load()?;
+        Self::load_ron(
+            assets::res_dir().join("prefabs.ron"),
+            &interactions,
+            catalogue,
+        )
+    }
+
+    pub fn load_ron<P: AsRef<Path>>(
+        path: P,
+        interactions: &Interactions,
+        catalogue: &AssetCatalogue,
+    ) -> Result<Self> {
+        let source = std::fs::read_to_string(path.as_ref())
+            .with_context(|| format!("Can't load {:?}", path.as_ref()))?;
+        Self::parse(&source, interactions, catalogue)
+            .with_context(|| format!("Invalid {:?}", path.as_ref()))
+    }
+
+    pub fn parse(
+        source: &str,
+        interactions: &Interactions,
+        catalogue: &AssetCatalogue,
+    ) -> Result<Self> {
+        let defs: BTreeMap<String, PrefabDef> =
+            ron::de::from_str(source).map_err(|error| anyhow!("{}", error))?;
+
+        let mut prefabs = BTreeMap::new();
+        for (name, def) in defs.iter() {
+            let prefab = resolve(name, def, &defs, interactions, catalogue)?;
+            prefabs.insert(name.clone(), prefab);
+        }
+
+        for name in REQUIRED_PREFABS {
+            if !prefabs.contains_key(name) {
+                bail!(
+                    "Prefab `{}` is missing, the game can't run without it",
+                    name
+                );
+            }
+        }
+
+        Ok(Self { prefabs })
+    }
+
+    /// Panics for unknown names: every name the game uses is checked when loading
+    pub fn get(&self, name: &str) -> &Prefab {
+        self.prefabs
+            .get(name)
+            .unwrap_or_else(|| panic!("Prefab `{}` is not defined", name))
+    }
+}
+
+fn invalid(prefab: &str, field: &str, problem: impl Display) -> Error {
+    anyhow!("Prefab `{}`, field `{}`: {}", prefab, field, problem)
+}
+
+fn check_positive(prefab: &str, field: &str, value: f32) -> Result<()> {
+    if value.is_finite() && value > 0. {
+        Ok(())
+    } else {
+        Err(invalid(
+            prefab,
+            field,
+            format!("must be positive, got {}", value),
+        ))
+    }
+}
+
+fn check_non_negative(prefab: &str, field: &str, value: f32) -> Result<()> {
+    if value.is_finite() && value >= 0. {
+        Ok(())
+    } else {
+        Err(invalid(
+            prefab,
+            field,
+            format!("can't be negative, got {}", value),
+        ))
+    }
+}
+
+fn resolve(
+    name: &str,
+    def: &PrefabDef,
+    defs: &BTreeMap<String, PrefabDef>,
+    interactions: &Interactions,
+    catalogue: &AssetCatalogue,
+) -> Result<Prefab> {
+    let renderable = match &def.renderable {
+        Some(renderable) => {
+            let (mesh_id, mesh) =
+                catalogue
+                    .get_mesh_by_name(&renderable.mesh)
+                    .ok_or_else(|| {
+                        invalid(
+                            name,
+                            "renderable.mesh",
+                            format!("there is no mesh `{}` in the assets", renderable.mesh),
+                        )
+                    })?;
+            check_positive(name, "renderable.scale", renderable.scale)?;
+            Some(Renderable {
+                shader: renderable.shader,
+                mesh: mesh_id,
+                material: mesh.material,
+                scale: renderable.scale,
+            })
+        }
+        None => None,
+    };
+
+    if let Some(physics) = &def.physics {
+        check_positive(name, "physics.max_linear_speed", physics.max_linear_speed)?;
+        check_non_negative(name, "physics.launch_speed", physics.launch_speed)?;
+        check_non_negative(name, "physics.linear_damping", physics.linear_damping)?;
+        check_non_negative(name, "physics.angular_damping", physics.angular_damping)?;
+        check_positive(name, "physics.mass", physics.mass)?;
+        if let Some(restitution) = physics.restitution {
+            if !(0.0..=1.0).contains(&restitution) {
+                return Err(invalid(
+                    name,
+                    "physics.restitution",
+                    format!("must be between 0 and 1, got {}", restitution),
+                ));
+            }
+        }
+        if let Some(random_speed) = &physics.random_speed {
+            check_positive(name, "physics.random_speed.linear", random_speed.linear)?;
+            check_positive(name, "physics.random_speed.angular", random_speed.angular)?;
+        }
+    }
+
+    let shape = match def.shape {
+        Some(ShapeDef::Circle { radius }) => {
+            check_positive(name, "shape.radius", radius)?;
+            Some(PrefabShape::Circle { radius })
+        }
+        Some(ShapeDef::Capsule {
+            half_length,
+            radius,
+        }) => {
+            check_non_negative(name, "shape.half_length", half_length)?;
+            check_positive(name, "shape.radius", radius)?;
+            Some(PrefabShape::Capsule {
+                half_length,
+                radius,
+            })
+        }
+        Some(ShapeDef::Hull) => {
+            let mesh = def
+                .renderable
+                .as_ref()
+                .and_then(|renderable| catalogue.get_mesh_by_name(&renderable.mesh))
+                .map(|(_id, mesh)| mesh)
+                .ok_or_else(|| invalid(name, "shape", "a hull needs a renderable mesh"))?;
+            if mesh.hull.len() < 3 {
+                return Err(invalid(
+                    name,
+                    "shape",
+                    format!("mesh `{}` is flat seen from above", mesh.name),
+                ));
+            }
+            Some(PrefabShape::Polygon {
+                points: Arc::from(mesh.hull.as_slice()),
+            })
+        }
+        None => None,
+    };
+
+    if let Some(light) = &def.light {
+        check_non_negative(name, "light.radius", light.radius)?;
+    }
+
+    let lifetime = match def.lifetime {
+        Some(seconds) => {
+            check_non_negative(name, "lifetime", seconds)?;
+            Some(Lifetime {
+                dies_after: Duration::from_secs_f32(seconds),
+            })
+        }
+        None => None,
+    };
+
+    let hyperspace = match &def.hyperspace {
+        Some(hyperspace) => {
+            check_non_negative(name, "hyperspace.delay", hyperspace.delay)?;
+            check_non_negative(name, "hyperspace.cooldown", hyperspace.cooldown)?;
+            if !(0.0..=1.0).contains(&hyperspace.self_destruct_chance) {
+                return Err(invalid(
+                    name,
+                    "hyperspace.self_destruct_chance",
+                    format!(
+                        "must be between 0 and 1, got {}",
+                        hyperspace.self_destruct_chance
+                    ),
+                ));
+            }
+            Some(Hyperspace {
+                delay: Duration::from_secs_f32(hyperspace.delay),
+                cooldown: Duration::from_secs_f32(hyperspace.cooldown),
+                self_destruct_chance: hyperspace.self_destruct_chance,
+                reappears_in: None,
+                ready_in: Duration::ZERO,
+            })
+        }
+        None => None,
+    };
+
+    let shield = match &def.shield {
+        Some(shield) => {
+            check_positive(name, "shield.energy", shield.energy)?;
+            check_non_negative(name, "shield.drain", shield.drain)?;
+            check_non_negative(name, "shield.recharge", shield.recharge)?;
+            Some(Shield::new(shield.energy, shield.drain, shield.recharge))
+        }
+        None => None,
+    };
+
+    let ai = match &def.ai {
+        Some(ai) => {
+            check_positive(name, "ai.speed", ai.speed)?;
+            check_positive(name, "ai.course_change_interval", ai.course_change_interval)?;
+            check_non_negative(name, "ai.aim_error", ai.aim_error)?;
+            let course_change_interval = Duration::from_secs_f32(ai.course_change_interval);
+            Some(Ai {
+                speed: ai.speed,
+                course_change_interval,
+                aim_error: ai.aim_error,
+                heading: cgmath::vec2(1., 0.),
+                course_change_in: course_change_interval,
+            })
+        }
+        None => None,
+    };
+
+    let weapon = match &def.weapon {
+        Some(weapon) => {
+            if !defs.contains_key(&weapon.projectile) {
+                return Err(invalid(
+                    name,
+                    "weapon.projectile",
+                    format!("there is no prefab `{}`", weapon.projectile),
+                ));
+            }
+            check_positive(name, "weapon.fire_interval", weapon.fire_interval)?;
+            if weapon.spread_count == 0 {
+                return Err(invalid(name, "weapon.spread_count", "must be at least 1"));
+            }
+            check_non_negative(name, "weapon.spread_angle", weapon.spread_angle)?;
+            check_non_negative(name, "weapon.muzzle_offset", weapon.muzzle_offset)?;
+            if !(0.0..=1.0).contains(&weapon.heat_per_shot) {
+                return Err(invalid(
+                    name,
+                    "weapon.heat_per_shot",
+                    format!("must be between 0 and 1, got {}", weapon.heat_per_shot),
+                ));
+            }
+            check_non_negative(name, "weapon.cooling", weapon.cooling)?;
+            if weapon.heat_per_shot > 0. && weapon.cooling == 0. {
+                return Err(invalid(
+                    name,
+                    "weapon.cooling",
+                    "must be positive when shots heat the weapon up",
+                ));
+            }
+            Some(Weapon {
+                spread_count: weapon.spread_count,
+                spread_angle: weapon.spread_angle,
+                muzzle_offset: weapon.muzzle_offset,
+                ammo: weapon.ammo,
+                heat_per_shot: weapon.heat_per_shot,
+                cooling: weapon.cooling,
+                // Like entity names, projectile names are static
+                ..Weapon::new(
+                    Box::leak(weapon.projectile.clone().into_boxed_str()),
+                    Duration::from_secs_f32(weapon.fire_interval),
+                )
+            })
+        }
+        None => None,
+    };
+
+    for (i, split) in def.splits_into.iter().enumerate() {
+        let field = format!("splits_into[{}].prefab", i);
+        if split.prefab == name {
+            return Err(invalid(name, &field, "a prefab can't split into itself"));
+        }
+        if !defs.contains_key(&split.prefab) {
+            return Err(invalid(
+                name,
+                &field,
+                format!("there is no prefab `{}`", split.prefab),
+            ));
+        }
+    }
+
+    if !def.splits_into.is_empty() && !matches!(def.on_collision, Some(CollisionBehaviour::Shatter))
+    {
+        return Err(invalid(
+            name,
+            "splits_into",
+            "only prefabs with `on_collision: Shatter` split",
+        ));
+    }
+
+    check_non_negative(name, "split_impulse", def.split_impulse)?;
+
+    if let Some(drops) = &def.drops {
+        if !(0.0..=1.0).contains(&drops.chance) {
+            return Err(invalid(
+                name,
+                "drops.chance",
+                format!("must be between 0 and 1, got {}", drops.chance),
+            ));
+        }
+        if drops.prefabs.is_empty() {
+            return Err(invalid(name, "drops.prefabs", "can't be empty"));
+        }
+        for (i, prefab) in drops.prefabs.iter().enumerate() {
+            if !defs.contains_key(prefab) {
+                return Err(invalid(
+                    name,
+                    &format!("drops.prefabs[{}]", i),
+                    format!("there is no prefab `{}`", prefab),
+                ));
+            }
+        }
+        if !matches!(def.on_collision, Some(CollisionBehaviour::Shatter)) {
+            return Err(invalid(
+                name,
+                "drops",
+                "only prefabs with `on_collision: Shatter` drop anything",
+            ));
+        }
+    }
+
+    // Callbacks that act on the kind of entity they're attached to
+    let kind = match def.on_collision {
+        Some(CollisionBehaviour::PowerUp) if !matches!(def.kind, Some(Kind::PowerUp { .. })) => {
+            Some("PowerUp")
+        }
+        Some(CollisionBehaviour::Ship) if def.kind != Some(Kind::Ship) => Some("Ship"),
+        _ => None,
+    };
+    if let Some(kind) = kind {
+        return Err(invalid(
+            name,
+            "kind",
+            format!(
+                "prefabs with `on_collision: {0}` must be of kind `{0}`",
+                kind
+            ),
+        ));
+    }
+
+    let layer = match (def.on_collision, def.layer) {
+        (Some(_), Some(layer)) => Some((layer, interactions.mask(layer))),
+        (None, None) => None,
+        (Some(_), None) => {
+            return Err(invalid(
+                name,
+                "layer",
+                "prefabs with `on_collision` need a layer to collide on",
+            ))
+        }
+        (None, Some(_)) => {
+            return Err(invalid(
+                name,
+                "layer",
+                "only prefabs with `on_collision` collide",
+            ))
+        }
+    };
+
+    if def.sensor && def.on_collision.is_none() {
+        return Err(invalid(
+            name,
+            "sensor",
+            "only prefabs with `on_collision` can be sensors",
+        ));
+    }
+
+    Ok(Prefab {
+        // Entity names are static, and prefabs are loaded once per run
+        name: Box::leak(name.to_owned().into_boxed_str()),
+        kind: def.kind,
+        renderable,
+        physics: def.physics,
+        shape,
+        light: def.light,
+        lifetime,
+        health: def.health.map(Health::new),
+        control: def.control,
+        hyperspace,
+        shield,
+        ai,
+        weapon,
+        on_collision: def.on_collision,
+        layer,
+        sensor: def.sensor,
+        entered_world: def.entered_world,
+        splits_into: def.splits_into.clone(),
+        split_impulse: def.split_impulse,
+        drops: def.drops.clone(),
+    })
+}
+
+#[test]
+fn test_prefabs_validation() {
+    let catalogue = AssetCatalogue::load().unwrap();
+    let interactions = Interactions::load().unwrap();
+
+    let prefabs = Prefabs::load(&catalogue).unwrap();
+    assert_eq!(prefabs.get("Asteroid_L").splits_into.len(), 3);
+    let (_layer, laser_mask) = prefabs.get("Laser").layer.unwrap();
+    assert_ne!(laser_mask & CollisionLayer::Asteroid.bit(), 0);
+    assert_eq!(laser_mask & CollisionLayer::PowerUp.bit(), 0);
+
+    let error = |source: &str| {
+        let required = REQUIRED_PREFABS
+            .iter()
+            .map(|name| format!("{:?}: (),", name))
+            .collect::<String>();
+        let source = format!(
+            r#"#![enable(implicit_some)]
+            {{
+                {}
+                {}
+            }}"#,
+            required, source
+        );
+        format!(
+            "{:#}",
+            Prefabs::parse(&source, &interactions, &catalogue)
+                .err()
+                .unwrap()
+        )
+    };
+
+    assert_eq!(
+        error(r#""Rock": (renderable: (mesh: "Rock", shader: Model))"#),
+        "Prefab `Rock`, field `renderable.mesh`: there is no mesh `Rock` in the assets"
+    );
+    assert_eq!(
+        error(r#""Rock": (shape: Circle(radius: -1.0))"#),
+        "Prefab `Rock`, field `shape.radius`: must be positive, got -1"
+    );
+    assert_eq!(
+        error(r#""Rock": (shape: Hull)"#),
+        "Prefab `Rock`, field `shape`: a hull needs a renderable mesh"
+    );
+    assert_eq!(
+        error(r#""Rock": (on_collision: Shatter, splits_into: [(prefab: "Pebble")])"#),
+        "Prefab `Rock`, field `splits_into[0].prefab`: there is no prefab `Pebble`"
+    );
+    assert_eq!(
+        error(r#""Rock": (on_collision: Shatter, drops: (chance: 1.5, prefabs: ["Laser"]))"#),
+        "Prefab `Rock`, field `drops.chance`: must be between 0 and 1, got 1.5"
+    );
+    assert_eq!(
+        error(r#""Gun": (weapon: (projectile: "Bullet", fire_interval: 0.1))"#),
+        "Prefab `Gun`, field `weapon.projectile`: there is no prefab `Bullet`"
+    );
+    assert_eq!(
+        error(r#""Rock": (shape: Circle(radius: 1.0), on_collision: Shatter)"#),
+        "Prefab `Rock`, field `layer`: prefabs with `on_collision` need a layer to collide on"
+    );
+    assert_eq!(
+        error(r#""Pickup": (on_collision: PowerUp, layer: PowerUp)"#),
+        "Prefab `Pickup`, field `kind`: prefabs with `on_collision: PowerUp` must be of kind `PowerUp`"
+    );
+    assert_eq!(
+        error(r#""Ufo": (kind: Asteroid(size: Small), on_collision: Ship, layer: Ship)"#),
+        "Prefab `Ufo`, field `kind`: prefabs with `on_collision: Ship` must be of kind `Ship`"
+    );
+    assert_eq!(
+        error(r#""Zone": (sensor: true)"#),
+        "Prefab `Zone`, field `sensor`: only prefabs with `on_collision` can be sensors"
+    );
+    assert!(error(r#""Rock": (radius: 1.0)"#).contains("radius"));
+}
@@ -0,0 +1,146 @@
+use super::components::{
+    Ai, Collision, Control, Health, Hyperspace, Kind, Lifetime, Light, Modifiers, Physics,
+    Renderable, Shield, Weapon,
+};
+use super::entity::Entity;
+use super::geometry::Shape;
+use super::storage::{EntityId, EntityStorage};
+use super::world::WorldPosition;
+
+/// Disjoint borrows of an entity's fields, so that a query can hand out several of them at once.
+/// A component is taken out when fetched, which is why each component can appear in a query only once.
+pub struct EntityParts<'a> {
+    pub position: &'a WorldPosition,
+    pub rotation: Option<&'a mut cgmath::Quaternion<f32>>,
+    pub kind: Option<&'a mut Kind>,
+    pub shape: Option<&'a mut Shape>,
+    pub renderable: Option<&'a mut Renderable>,
+    pub physics: Option<&'a mut Physics>,
+    pub collision: Option<&'a mut Collision>,
+    pub control: Option<&'a mut Control>,
+    pub health: Option<&'a mut Health>,
+    pub hyperspace: Option<&'a mut Hyperspace>,
+    pub ai: Option<&'a mut Ai>,
+    pub modifiers: Option<&'a mut Modifiers>,
+    pub weapon: Option<&'a mut Weapon>,
+    pub shield: Option<&'a mut Shield>,
+    pub lifetime: Option<&'a mut Lifetime>,
+    pub light: Option<&'a mut Light>,
+}
+
+/// Something that can be fetched from an entity: `&T`, `&mut T` or `Option<..>` of a component,
+/// or a tuple of those. Entities that miss a required component are skipped.
+///
+/// ```ignore
+/// for (id, (physics, control)) in storage.query::<(&mut Physics, &Control)>() { .. }
+/// ```
+pub trait Query<'a>: Sized {
+    fn fetch(parts: &mut EntityParts<'a>) -> Option<Self>;
+}
+
+macro_rules! impl_component_query {
+    ($component:ty, $field:ident) => {
+        impl<'a> Query<'a> for &'a $component {
+            fn fetch(parts: &mut EntityParts<'a>) -> Option<Self> {
+                parts.$field.take().map(|component| &*component)
+            }
+        }
+
+        impl<'a> Query<'a> for &'a mut $component {
+            fn fetch(parts: &mut EntityParts<'a>) -> Option<Self> {
+                parts.$field.take()
+            }
+        }
+    };
+}
+
+impl_component_query!(cgmath::Quaternion<f32>, rotation);
+impl_component_query!(Kind, kind);
+impl_component_query!(Shape, shape);
+impl_component_query!(Renderable, renderable);
+impl_component_query!(Physics, physics);
+impl_component_query!(Collision, collision);
+impl_component_query!(Control, control);
+impl_component_query!(Health, health);
+impl_component_query!(Hyperspace, hyperspace);
+impl_component_query!(Ai, ai);
+impl_component_query!(Modifiers, modifiers);
+impl_component_query!(Weapon, weapon);
+impl_component_query!(Shield, shield);
+impl_component_query!(Lifetime, lifetime);
+impl_component_query!(Light, light);
+
+/// Position is read-only: moving entities goes through `Entity::update_physics`,
+/// which keeps world wrapping consistent
+impl<'a> Query<'a> for &'a WorldPosition {
+    fn fetch(parts: &mut EntityParts<'a>) -> Option<Self> {
+        Some(parts.position)
+    }
+}
+
+/// Optional components don't filter entities out
+impl<'a, Q: Query<'a>> Query<'a> for Option<Q> {
+    fn fetch(parts: &mut EntityParts<'a>) -> Option<Self> {
+        Some(Q::fetch(parts))
+    }
+}
+
+macro_rules! impl_tuple_query {
+    ($($q:ident),+) => {
+        impl<'a, $($q: Query<'a>),+> Query<'a> for ($($q,)+) {
+            fn fetch(parts: &mut EntityParts<'a>) -> Option<Self> {
+                Some(($($q::fetch(parts)?,)+))
+            }
+        }
+    };
+}
+
+impl_tuple_query!(A);
+impl_tuple_query!(A, B);
+impl_tuple_query!(A, B, C);
+impl_tuple_query!(A, B, C, D);
+impl_tuple_query!(A, B, C, D, E);
+impl_tuple_query!(A, B, C, D, E, F);
+impl_tuple_query!(A, B, C, D, E, F, G);
+
+impl EntityStorage {
+    pub fn query<'a, Q: Query<'a>>(&'a mut self) -> impl Iterator<Item = (EntityId, Q)> + 'a {
+        self.iter_mut_with_ids()
+            .filter_map(|(id, entity)| fetch(entity).map(|item| (id, item)))
+    }
+}
+
+fn fetch<'a, Q: Query<'a>>(entity: &'a mut Entity) -> Option<Q> {
+    Q::fetch(&mut entity.parts())
+}
+
+#[test]
+fn test_query() {
+    let position = WorldPosition::default();
+    let mut moving = Entity::new("Moving", position);
+    moving.physics = Some(Physics::default());
+    let mut controlled = Entity::new("Controlled", position);
+    controlled.physics = Some(Physics::default());
+    controlled.control = Some(Control::enabled());
+    let still = Entity::new("Still", position);
+
+    let mut storage = EntityStorage::from(vec![Some(moving), None, Some(still), Some(controlled)]);
+
+    for (_id, (physics, control)) in storage.query::<(&mut Physics, Option<&Control>)>() {
+        if control.is_some() {
+            physics.linear_speed = (1.0, 0.0).into();
+        }
+    }
+    assert_eq!(storage.query::<&Physics>().count(), 2);
+    assert_eq!(storage.query::<(&Physics, &Control)>().count(), 1);
+    assert_eq!(
+        storage
+            .query::<&Physics>()
+            .filter(|(_id, physics)| physics.linear_speed.x == 1.0)
+            .count(),
+        1
+    );
+
+    // A component can't be borrowed twice
+    assert_eq!(storage.query::<(&Physics, &Physics)>().count(), 0);
+}
@@ -0,0 +1,150 @@
+use rayon::prelude::*;
+
+use super::entity::Entity;
+
+/// Handle to an entity. Slots are reused after an entity is killed,
+/// but every reuse bumps the slot's generation, so old handles never resolve to a new entity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EntityId {
+    index: usize,
+    generation: u32,
+}
+
+#[derive(Default)]
+pub struct EntityStorage {
+    slots: Vec<Option<Entity>>,
+    generations: Vec<u32>,
+}
+
+impl EntityStorage {
+    pub fn insert(&mut self, entity: Entity) -> EntityId {
+        let first_vacant_index = self.slots.iter().position(Option::is_none);
+
+        let index = match first_vacant_index {
+            Some(index) => {
+                self.slots[index] = Some(entity);
+                index
+            }
+            None => {
+                self.slots.push(Some(entity));
+                self.generations.push(0);
+                self.slots.len() - 1
+            }
+        };
+
+        EntityId {
+            index,
+            generation: self.generations[index],
+        }
+    }
+
+    /// Returns the removed entity, or `None` if the handle is stale
+    pub fn remove(&mut self, id: EntityId) -> Option<Entity> {
+        self.get(id)?;
+        self.generations[id.index] += 1;
+        self.slots[id.index].take()
+    }
+
+    pub fn get(&self, id: EntityId) -> Option<&Entity> {
+        if self.generations.get(id.index) == Some(&id.generation) {
+            self.slots[id.index].as_ref()
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut Entity> {
+        if self.generations.get(id.index) == Some(&id.generation) {
+            self.slots[id.index].as_mut()
+        } else {
+            None
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Entity> {
+        self.slots.iter().flatten()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Entity> {
+        self.slots.iter_mut().flatten()
+    }
+
+    pub fn iter_with_ids(&self) -> impl Iterator<Item = (EntityId, &Entity)> {
+        let generations = &self.generations;
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, slot)| {
+                slot.as_ref().map(|entity| {
+                    let id = EntityId {
+                        index,
+                        generation: generations[index],
+                    };
+                    (id, entity)
+                })
+            })
+    }
+
+    pub fn iter_mut_with_ids(&mut self) -> impl Iterator<Item = (EntityId, &mut Entity)> {
+        let generations = &self.generations;
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(move |(index, slot)| {
+                slot.as_mut().map(|entity| {
+                    let id = EntityId {
+                        index,
+                        generation: generations[index],
+                    };
+                    (id, entity)
+                })
+            })
+    }
+
+    pub fn par_iter(&self) -> impl ParallelIterator<Item = &Entity> {
+        self.slots.par_iter().flatten()
+    }
+
+    pub fn par_iter_mut(&mut self) -> impl ParallelIterator<Item = &mut Entity> {
+        self.slots.par_iter_mut().flatten()
+    }
+}
+
+impl From<Vec<Option<Entity>>> for EntityStorage {
+    fn from(slots: Vec<Option<Entity>>) -> Self {
+        let generations = vec![0; slots.len()];
+        Self { slots, generations }
+    }
+}
+
+impl std::fmt::Debug for EntityStorage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.slots.iter()).finish()
+    }
+}
+
+#[test]
+fn test_entity_storage_stale_ids() {
+    use super::world::WorldPosition;
+
+    let mut storage = EntityStorage::default();
+    let a = storage.insert(Entity::new("A", WorldPosition::default()));
+    let b = storage.insert(Entity::new("B", WorldPosition::default()));
+
+    assert_eq!(storage.remove(a).map(|entity| entity.name), Some("A"));
+    assert!(storage.remove(a).is_none());
+
+    // The vacant slot is reused, but the old handle doesn't point to the new entity
+    let c = storage.insert(Entity::new("C", WorldPosition::default()));
+    assert_ne!(a, c);
+    assert!(storage.get(a).is_none());
+    assert_eq!(storage.get(c).map(|entity| entity.name), Some("C"));
+    assert_eq!(storage.get(b).map(|entity| entity.name), Some("B"));
+    assert_eq!(
+        storage
+            .iter_with_ids()
+            .map(|(id, _entity)| id)
+            .collect::<Vec<_>>(),
+        vec![c, b]
+    );
+}
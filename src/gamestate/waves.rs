@@ -0,0 +1,86 @@
+use std::time::Duration;
+
+const FIRST_WAVE_ASTEROIDS: usize = 3;
+const MAX_WAVE_ASTEROIDS: usize = 10;
+/// How much faster asteroids get with every level
+const SPEED_STEP: f32 = 0.15;
+const BREATHER: Duration = Duration::from_secs(3);
+
+/// Classic wave progression: clearing the field starts a short breather,
+/// after which the next level begins with more and faster asteroids
+#[derive(Default)]
+pub struct Waves {
+    level: usize,
+    breather: Option<Duration>,
+}
+
+impl Waves {
+    /// 0 before the first wave has started
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    pub fn in_breather(&self) -> bool {
+        self.breather.is_some()
+    }
+
+    /// Number of `Asteroid_L` the current level starts with
+    pub fn asteroids(&self) -> usize {
+        (FIRST_WAVE_ASTEROIDS + self.level.saturating_sub(1)).min(MAX_WAVE_ASTEROIDS)
+    }
+
+    /// Multiplier for the initial speed of the current level's asteroids
+    pub fn speed_factor(&self) -> f32 {
+        1. + SPEED_STEP * self.level.saturating_sub(1) as f32
+    }
+
+    /// Returns the new level when it's time to start it
+    pub fn update(&mut self, field_is_clear: bool, dtime: Duration) -> Option<usize> {
+        if self.level == 0 {
+            return Some(self.next_level());
+        }
+
+        if !field_is_clear {
+            return None;
+        }
+
+        let left = self.breather.unwrap_or(BREATHER);
+        if left > dtime {
+            self.breather = Some(left - dtime);
+            None
+        } else {
+            self.breather = None;
+            Some(self.next_level())
+        }
+    }
+
+    fn next_level(&mut self) -> usize {
+        self.level += 1;
+        self.level
+    }
+}
+
+#[test]
+fn test_waves_progression() {
+    let dtime = Duration::from_millis(500);
+    let mut waves = Waves::default();
+
+    assert_eq!(waves.update(true, dtime), Some(1));
+    assert_eq!(waves.asteroids(), 3);
+    assert_eq!(waves.update(false, dtime), None);
+
+    // Clearing the field gives a breather before the next level
+    let mut steps = 0;
+    let level = loop {
+        steps += 1;
+        if let Some(level) = waves.update(true, dtime) {
+            break level;
+        }
+        assert!(waves.in_breather());
+    };
+    assert_eq!(level, 2);
+    assert_eq!(dtime * steps, BREATHER);
+    assert!(!waves.in_breather());
+    assert_eq!(waves.asteroids(), 4);
+    assert!(waves.speed_factor() > 1.);
+}
@@ -65,8 +65,8 @@ impl World {
     }
 
     /// Add fake instances to make the world visually looping
-    pub(crate) fn add_ghost_instances(&self, entity: &Entity) -> Vec<Instance> {
-        let instance = entity.to_instance();
+    pub(crate) fn add_ghost_instances(&self, entity: &Entity, alpha: f32) -> Vec<Instance> {
+        let instance = entity.to_instance(alpha);
         if !entity.entered_world() {
             return vec![instance];
         }
@@ -150,6 +150,40 @@ impl WorldPosition {
         ))
     }
 
+    /// Shortest vector from `self` to `other`, taking world wrapping into account
+    pub fn delta(&self, other: &Self) -> cgmath::Vector2<f32> {
+        let (w, h) = self.world_size;
+        let wrap = |d: f32, world: f32| {
+            if d > world / 2. {
+                d - world
+            } else if d < -world / 2. {
+                d + world
+            } else {
+                d
+            }
+        };
+
+        let d = other.position - self.position;
+        cgmath::vec2(wrap(d.x, w), wrap(d.y, h))
+    }
+
+    /// Linear interpolation along the shortest path, so that crossing the world's edge
+    /// doesn't make an object fly across the whole screen
+    pub fn lerp(&self, other: &Self, alpha: f32) -> Self {
+        if self.position == other.position {
+            return *other;
+        }
+
+        // Positions outside of the world bounds (entering objects) are interpolated as is
+        if !self.world_rect().contains_point(self.to_tuple())
+            || !other.world_rect().contains_point(other.to_tuple())
+        {
+            return self.translate_unsafe((other.position - self.position) * alpha);
+        }
+
+        self.translate(self.delta(other) * alpha)
+    }
+
     pub fn to_zero(&self) -> Self {
         Self {
             position: (0.0, 0.0).into(),
@@ -229,6 +263,20 @@ fn test_world_position_normalize() {
     );
 }
 
+#[test]
+fn test_world_position_lerp() {
+    let mut a = WorldPosition::default();
+    let mut b = WorldPosition::default();
+
+    a.position = cgmath::vec2(45., 0.);
+    b.position = cgmath::vec2(-45., 0.);
+
+    assert_eq!(a.lerp(&b, 0.).position.x, 45.);
+    assert_eq!(a.lerp(&b, 0.25).position.x, 47.5);
+    assert_eq!(a.lerp(&b, 0.75).position.x, -47.5);
+    assert_eq!(a.lerp(&b, 1.).position.x, -45.);
+}
+
 #[test]
 fn test_world_position_translate() {
     let world_postion = WorldPosition::default();
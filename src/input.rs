@@ -1,5 +1,6 @@
 use winit::event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent};
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Input {
     pub is_up_pressed: bool,
     pub is_down_pressed: bool,
@@ -21,6 +22,33 @@ impl Input {
         }
     }
 
+    /// Pack pressed buttons into a bitmask, one bit per button
+    pub fn to_bits(self) -> u8 {
+        [
+            self.is_up_pressed,
+            self.is_down_pressed,
+            self.is_forward_pressed,
+            self.is_backward_pressed,
+            self.is_left_pressed,
+            self.is_right_pressed,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (bit, pressed)| bits | ((*pressed as u8) << bit))
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        let pressed = |bit: u8| bits & (1 << bit) != 0;
+        Self {
+            is_up_pressed: pressed(0),
+            is_down_pressed: pressed(1),
+            is_forward_pressed: pressed(2),
+            is_backward_pressed: pressed(3),
+            is_left_pressed: pressed(4),
+            is_right_pressed: pressed(5),
+        }
+    }
+
     pub fn process_events(&mut self, event: &WindowEvent) -> bool {
         match event {
             WindowEvent::KeyboardInput {
@@ -65,3 +93,13 @@ impl Input {
         }
     }
 }
+
+#[test]
+fn test_input_bits() {
+    let mut input = Input::new();
+    assert_eq!(input.to_bits(), 0);
+
+    input.is_forward_pressed = true;
+    input.is_right_pressed = true;
+    assert_eq!(Input::from_bits(input.to_bits()), input);
+}
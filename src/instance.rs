@@ -4,6 +4,7 @@ use cgmath::Zero;
 pub struct Instance {
     pub(crate) position: cgmath::Vector3<f32>,
     pub(crate) rotation: cgmath::Quaternion<f32>,
+    pub(crate) scale: f32,
 }
 
 impl Default for Instance {
@@ -11,6 +12,7 @@ impl Default for Instance {
         Self {
             position: (0.0, 0.0, 0.0).into(),
             rotation: cgmath::Quaternion::zero(),
+            scale: 1.0,
         }
     }
 }
@@ -26,7 +28,8 @@ impl Instance {
     pub fn to_raw(&self) -> InstanceRaw {
         InstanceRaw {
             model: (cgmath::Matrix4::from_translation(self.position)
-                * cgmath::Matrix4::from(self.rotation))
+                * cgmath::Matrix4::from(self.rotation)
+                * cgmath::Matrix4::from_scale(self.scale))
             .into(),
             normal: cgmath::Matrix3::from(self.rotation).into(),
         }
@@ -1,5 +1,6 @@
 pub static MODE: Mode = Mode::Dev;
 
+mod assets;
 mod backdrop;
 mod camera;
 mod font;
@@ -8,6 +9,7 @@ mod input;
 mod instance;
 mod light;
 mod model;
+mod replay;
 mod resource;
 mod shaders;
 mod state;
@@ -38,6 +40,18 @@ fn debug(str: &str) {
 
 fn main() {
     env_logger::init();
+
+    let args = std::env::args().collect::<Vec<_>>();
+    if let [_, flag, path] = args.as_slice() {
+        if flag == "--replay" {
+            if let Err(e) = replay::run(path) {
+                eprintln!("{:?}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+    }
+
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
         .with_title("Asteroids")
@@ -62,7 +76,10 @@ fn main() {
                                 ..
                             },
                         ..
-                    } => *control_flow = ControlFlow::Exit,
+                    } => {
+                        state.finish_recording();
+                        *control_flow = ControlFlow::Exit
+                    }
 
                     WindowEvent::Resized(physical_size) => {
                         state.resize(*physical_size);
@@ -1,9 +1,13 @@
-use crate::{camera::CameraBuffer, light::LightsBuffer, texture};
+use crate::{
+    assets::{mesh_hull, obj_load_options},
+    camera::CameraBuffer,
+    light::LightsBuffer,
+    texture,
+};
 use anyhow::*;
 use cgmath::{InnerSpace, Vector2, Vector3};
 use rayon::prelude::*;
 use std::{ops::Range, path::Path};
-use tobj::LoadOptions;
 use wgpu::{util::DeviceExt, BindGroup};
 
 pub trait Vertex {
@@ -69,6 +73,8 @@ pub struct Mesh {
     pub index_buffer: wgpu::Buffer,
     pub num_elements: u32,
     pub material: usize,
+    /// Outline for collision shapes, see `assets::mesh_hull`
+    pub hull: Vec<Vector2<f32>>,
 }
 
 pub struct Material {
@@ -147,14 +153,7 @@ impl Model {
         layout: &wgpu::BindGroupLayout,
         path: P,
     ) -> Result<Self> {
-        let (obj_models, obj_materials) = tobj::load_obj(
-            path.as_ref(),
-            &LoadOptions {
-                triangulate: true,
-                single_index: true,
-                ..Default::default()
-            },
-        )?;
+        let (obj_models, obj_materials) = tobj::load_obj(path.as_ref(), &obj_load_options())?;
 
         let obj_materials = obj_materials?;
 
@@ -319,6 +318,7 @@ impl Model {
                     index_buffer,
                     num_elements: m.mesh.indices.len() as u32,
                     material: m.mesh.material_id.unwrap_or(0),
+                    hull: mesh_hull(&m.mesh),
                 })
             })
             .collect::<Result<Vec<Mesh>>>()?;
@@ -0,0 +1,196 @@
+use anyhow::*;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::assets::AssetCatalogue;
+use crate::gamestate::prefab::Prefabs;
+use crate::gamestate::GameState;
+use crate::input::Input;
+
+/// File format:
+///
+/// ```text
+/// magic    [u8; 8]   "ASTEROID"
+/// version  u16
+/// seed     u64
+/// aspect   f32
+/// ticks    u32
+/// inputs   [u8; ticks]  one `Input::to_bits` per simulation step
+/// ```
+///
+/// All numbers are little-endian.
+const MAGIC: &[u8; 8] = b"ASTEROID";
+const VERSION: u16 = 1;
+
+/// A play session: everything needed to reproduce it step by step
+#[derive(Debug, PartialEq)]
+pub struct Recording {
+    pub seed: u64,
+    pub aspect: f32,
+    pub inputs: Vec<Input>,
+}
+
+impl Recording {
+    pub fn new(seed: u64, aspect: f32) -> Self {
+        Self {
+            seed,
+            aspect,
+            inputs: vec![],
+        }
+    }
+
+    /// Store `input` for each of the `steps` simulation steps made in a frame
+    pub fn record(&mut self, input: &Input, steps: usize) {
+        self.inputs.extend(std::iter::repeat(*input).take(steps));
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&self.seed.to_le_bytes())?;
+        writer.write_all(&self.aspect.to_le_bytes())?;
+        writer.write_all(&(self.inputs.len() as u32).to_le_bytes())?;
+        writer.write_all(
+            &self
+                .inputs
+                .iter()
+                .map(|input| input.to_bits())
+                .collect::<Vec<_>>(),
+        )?;
+
+        Ok(())
+    }
+
+    pub fn read(reader: &mut impl Read) -> Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        ensure!(&magic == MAGIC, "Not a replay file");
+
+        let version = u16::from_le_bytes(read_bytes(reader)?);
+        ensure!(
+            version == VERSION,
+            "Unsupported replay version {}, expected {}",
+            version,
+            VERSION
+        );
+
+        let seed = u64::from_le_bytes(read_bytes(reader)?);
+        let aspect = f32::from_le_bytes(read_bytes(reader)?);
+        let ticks = u32::from_le_bytes(read_bytes(reader)?) as usize;
+
+        let mut bits = vec![0; ticks];
+        reader
+            .read_exact(&mut bits)
+            .context("Replay file is truncated")?;
+
+        Ok(Self {
+            seed,
+            aspect,
+            inputs: bits.into_iter().map(Input::from_bits).collect(),
+        })
+    }
+
+    /// Save into `dir` under a name derived from the seed. Returns the path of the file.
+    pub fn save_to_dir<P: AsRef<Path>>(&self, dir: P) -> Result<PathBuf> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        let path = dir.as_ref().join(format!("{:016x}.replay", self.seed));
+        let mut writer = BufWriter::new(File::create(&path)?);
+        self.write(&mut writer)?;
+        writer.flush()?;
+
+        Ok(path)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut reader = BufReader::new(
+            File::open(path.as_ref())
+                .with_context(|| format!("Can't open replay {:?}", path.as_ref()))?,
+        );
+        Self::read(&mut reader)
+    }
+
+    /// Feed recorded inputs into a fresh game, one per simulation step
+    pub fn replay(&self, prefabs: Rc<Prefabs>) -> GameState {
+        let mut gamestate = GameState::new_game(self.aspect, prefabs, false, self.seed);
+        for input in &self.inputs {
+            gamestate.step(input);
+            // Nobody listens to events here, and a long session would pile them up
+            gamestate.drain_events();
+        }
+
+        gamestate
+    }
+}
+
+fn read_bytes<const N: usize>(reader: &mut impl Read) -> Result<[u8; N]> {
+    let mut bytes = [0; N];
+    reader
+        .read_exact(&mut bytes)
+        .context("Replay file is truncated")?;
+    Ok(bytes)
+}
+
+/// Play a recorded session back without opening a window and print the outcome
+pub fn run<P: AsRef<Path>>(path: P) -> Result<()> {
+    let recording = Recording::load(path)?;
+    let prefabs = Rc::new(Prefabs::load(&AssetCatalogue::load()?)?);
+
+    let gamestate = recording.replay(prefabs);
+
+    println!("Steps: {}", recording.inputs.len());
+    println!("Score: {}", gamestate.score());
+    for (name, entities) in gamestate.entities_grouped_by_name() {
+        println!("{}: {}", name, entities.len());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_recording_roundtrip() {
+    let mut recording = Recording::new(42, 1.5);
+    let mut input = Input::new();
+    recording.record(&input, 2);
+    input.is_forward_pressed = true;
+    recording.record(&input, 0);
+    recording.record(&input, 1);
+
+    let mut bytes = vec![];
+    recording.write(&mut bytes).unwrap();
+    assert_eq!(bytes.len(), 8 + 2 + 8 + 4 + 4 + 3);
+
+    let restored = Recording::read(&mut bytes.as_slice()).unwrap();
+    assert_eq!(restored, recording);
+    assert!(restored.inputs[2].is_forward_pressed);
+
+    // Unknown versions are rejected
+    bytes[8] = 2;
+    assert!(Recording::read(&mut bytes.as_slice()).is_err());
+}
+
+#[test]
+fn test_replay_reproduces_session() {
+    let prefabs = crate::gamestate::test_prefabs();
+    let mut gamestate = GameState::new_game(1.5, prefabs.clone(), false, 7);
+    let mut recording = Recording::new(7, 1.5);
+
+    // Spin around and shoot for a while
+    let mut input = Input::new();
+    for step in 0..2000 {
+        input.is_left_pressed = step % 300 < 200;
+        input.is_backward_pressed = step % 50 < 25;
+        input.is_forward_pressed = step % 500 < 20;
+        gamestate.step(&input);
+        recording.record(&input, 1);
+    }
+
+    let replayed = recording.replay(prefabs);
+
+    assert_eq!(replayed.score(), gamestate.score());
+    assert_eq!(
+        format!("{:?}", replayed.entities_grouped_by_name()),
+        format!("{:?}", gamestate.entities_grouped_by_name())
+    );
+}
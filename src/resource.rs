@@ -1,6 +1,7 @@
 use anyhow::*;
 
 use crate::{
+    assets::{self, AssetCatalogue, MeshInfo},
     model::{Material, Mesh, Model},
     texture,
 };
@@ -11,13 +12,8 @@ pub struct Resources {
 }
 
 impl Resources {
-    pub const ZERO: Self = Resources {
-        meshes: vec![],
-        materials: vec![],
-    };
-
     pub fn load(device: &wgpu::Device, queue: &wgpu::Queue) -> Result<Self> {
-        let res_dir = std::path::Path::new(env!("OUT_DIR")).join("res");
+        let res_dir = assets::res_dir();
 
         let texture_bind_group_layout = device.create_bind_group_layout(&texture::Texture::desc());
 
@@ -34,13 +30,18 @@ impl Resources {
         Ok(Self { meshes, materials })
     }
 
-    pub fn get_mesh_by_name(&self, name: &str) -> Option<(usize, &Mesh)> {
-        self.meshes.iter().enumerate().find_map(|(id, mesh)| {
-            if mesh.name == name {
-                Some((id, mesh))
-            } else {
-                None
-            }
-        })
+    /// GPU-free description of the loaded meshes for the game logic
+    pub fn catalogue(&self) -> AssetCatalogue {
+        AssetCatalogue {
+            meshes: self
+                .meshes
+                .iter()
+                .map(|mesh| MeshInfo {
+                    name: mesh.name.clone(),
+                    material: mesh.material,
+                    hull: mesh.hull.clone(),
+                })
+                .collect(),
+        }
     }
 }
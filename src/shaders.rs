@@ -1,10 +1,11 @@
 use crate::instance::InstanceRaw;
 use crate::model::{self, Vertex};
 use crate::texture::TextureVertex;
+use serde::Deserialize;
 use texture_shader;
 use wgpu;
 
-#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize)]
 pub enum ShaderName {
     Model,
     Texture,
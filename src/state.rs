@@ -4,10 +4,11 @@ use crate::{
     backdrop::Backdrop,
     camera::{self, CameraBuffer},
     debug,
-    gamestate::GameState,
+    gamestate::{prefab::Prefabs, GameState},
     input::Input,
     light::{self, LightsBuffer},
     model::DrawModel,
+    replay::Recording,
     resource::Resources,
     shaders::Shaders,
     texture,
@@ -15,7 +16,7 @@ use crate::{
 };
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use winit::{
-    event::{KeyboardInput, VirtualKeyCode, WindowEvent},
+    event::{ElementState, KeyboardInput, VirtualKeyCode, WindowEvent},
     window::Window,
 };
 
@@ -33,6 +34,7 @@ pub struct State {
     backdrop_renderer: Backdrop,
     gamestate: GameState,
     input: Input,
+    recording: Option<Recording>,
     last_renders: [Instant; 2],
     ui: UI,
     shaders: Shaders,
@@ -92,7 +94,8 @@ impl State {
         let shaders = Shaders::init(&device, config.format, Some(texture::Texture::DEPTH_FORMAT));
 
         let aspect = config.width as f32 / config.height as f32;
-        let mut gamestate = GameState::new_game(aspect, resources.clone(), true);
+        let prefabs = Rc::new(Prefabs::load(&resources.catalogue()).unwrap());
+        let mut gamestate = GameState::new_game(aspect, prefabs, true, rand::random());
 
         let mut camera_buffer = CameraBuffer::new(&device);
         camera_buffer.update_buffer(&queue, &mut gamestate.world.camera);
@@ -135,6 +138,7 @@ impl State {
             instance_buffer_size,
             last_renders,
             input,
+            recording: None,
             ui,
             shaders,
             resources,
@@ -158,18 +162,25 @@ impl State {
                 WindowEvent::KeyboardInput {
                     input:
                         KeyboardInput {
+                            state,
                             virtual_keycode: Some(keycode),
                             ..
                         },
                     ..
                 } => match keycode {
                     VirtualKeyCode::N => {
-                        let aspect = self.config.width as f32 / self.config.height as f32;
-                        self.gamestate = GameState::new_game(
-                            aspect,
-                            self.gamestate.entity_factory.resources.clone(),
-                            false,
-                        );
+                        if *state == ElementState::Pressed {
+                            let aspect = self.config.width as f32 / self.config.height as f32;
+                            let seed = rand::random();
+                            self.finish_recording();
+                            self.gamestate = GameState::new_game(
+                                aspect,
+                                self.gamestate.entity_factory.prefabs.clone(),
+                                false,
+                                seed,
+                            );
+                            self.recording = Some(Recording::new(seed, aspect));
+                        }
                         true
                     }
 
@@ -180,13 +191,14 @@ impl State {
     }
 
     pub fn update(&mut self) {
-        self.gamestate
-            .control_system(&self.input)
-            .lifetime_system()
-            .asteroids_spawn_system()
-            .physics_system()
-            .collision_system()
-            .submit();
+        let steps = self.gamestate.update(&self.input);
+        if let Some(recording) = &mut self.recording {
+            recording.record(&self.input, steps);
+        }
+
+        for event in self.gamestate.events() {
+            debug(&event.to_string());
+        }
 
         self.ui
             .update(&self.gamestate, self.fps(), &self.device, &self.queue);
@@ -335,6 +347,16 @@ impl State {
         Ok(())
     }
 
+    /// Save the current session, if any, so it can be played back with `--replay`
+    pub fn finish_recording(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            match recording.save_to_dir("replays") {
+                Ok(path) => println!("Session recorded to {:?}", path),
+                Err(e) => eprintln!("Failed to save the session: {:?}", e),
+            }
+        }
+    }
+
     fn fps(&self) -> u128 {
         let [last, previous] = self.last_renders;
         if last > previous {
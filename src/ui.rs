@@ -64,10 +64,63 @@ impl UI {
 
         if !gamestate.cutscene_mode {
             left_column.push(render_text(format!("Score: {:?}", gamestate.score())));
+            left_column.push(render_text(format!("Level: {:?}", gamestate.level())));
+            left_column.push(render_text(format!("Lives: {:?}", gamestate.lives())));
 
             gamestate
                 .spaceship_health()
                 .map(|health| left_column.push(render_text(format!("Health: {:?}", health.level))));
+
+            if let Some(hyperspace) = gamestate
+                .spaceship()
+                .and_then(|(_id, spaceship)| spaceship.hyperspace)
+            {
+                let status = if hyperspace.is_ready() {
+                    "ready".to_string()
+                } else if hyperspace.is_jumping() {
+                    "jumping".to_string()
+                } else {
+                    format!("{:.1}s", hyperspace.ready_in.as_secs_f32())
+                };
+                left_column.push(render_text(format!("Hyperspace: {}", status)));
+            }
+
+            if let Some(shield) = gamestate
+                .spaceship()
+                .and_then(|(_id, spaceship)| spaceship.shield)
+            {
+                let bar_length = 10;
+                let filled = (shield.charge() * bar_length as f32).round() as usize;
+                left_column.push(render_text(format!(
+                    "Shield: [{}{}]",
+                    "#".repeat(filled),
+                    "-".repeat(bar_length - filled)
+                )));
+            }
+
+            if let Some((_id, spaceship)) = gamestate.spaceship() {
+                let modifiers = spaceship.modifiers.unwrap_or_default();
+                let timed = [
+                    ("Spread", modifiers.spread_for),
+                    ("Rapid fire", modifiers.rapid_fire_for),
+                    (
+                        "Invincible",
+                        spaceship
+                            .health
+                            .map(|health| health.invincible_for)
+                            .unwrap_or_default(),
+                    ),
+                ];
+                for (name, left) in timed {
+                    if !left.is_zero() {
+                        left_column.push(render_text(format!(
+                            "{}: {:.0}s",
+                            name,
+                            left.as_secs_f32()
+                        )));
+                    }
+                }
+            }
         }
 
         let right_column = vec![render_text(format!("{:?} FPS", fps))];
@@ -77,6 +130,11 @@ impl UI {
                 render_text("Game Over".to_string()),
                 render_text("Press N for new game".to_string()),
             ]
+        } else if gamestate.is_between_levels() && !gamestate.cutscene_mode {
+            vec![
+                render_text("Well done!".to_string()),
+                render_text(format!("Level {} is coming", gamestate.level() + 1)),
+            ]
         } else if gamestate.cutscene_mode {
             vec![
                 render_text("Asteroids".to_string()),
@@ -84,6 +142,8 @@ impl UI {
                 render_text("A, D to turn".to_string()),
                 render_text("W to throttle".to_string()),
                 render_text("S to shoot".to_string()),
+                render_text("Space to jump to hyperspace".to_string()),
+                render_text("LShift to raise the shield".to_string()),
             ]
         } else {
             vec![]